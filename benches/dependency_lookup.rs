@@ -0,0 +1,64 @@
+//! Measures how long dependency lookups take with hundreds of registered
+//! services. Run with `cargo bench --bench dependency_lookup`.
+use std::ffi::CString;
+use std::hint::black_box;
+use std::time::Instant;
+
+use gtk::gio;
+use gtk::glib::{self, gobject_ffi, translate::*, StaticType};
+use rose_gtk::prelude::*;
+use rose_gtk::Application;
+
+const LOOKUPS: u32 = 100_000;
+
+/// Registers a new GObject type so every service has its own type
+fn register_service_type(name: &str) -> glib::Type {
+    let name = CString::new(name).unwrap();
+    unsafe {
+        from_glib(gobject_ffi::g_type_register_static_simple(
+            gobject_ffi::G_TYPE_OBJECT,
+            name.as_ptr(),
+            std::mem::size_of::<gobject_ffi::GObjectClass>() as u32,
+            None,
+            std::mem::size_of::<gobject_ffi::GObject>() as u32,
+            None,
+            0,
+        ))
+    }
+}
+
+fn measure(name: &str, services: usize, mut lookup: impl FnMut(u32)) {
+    let start = Instant::now();
+    for i in 0..LOOKUPS {
+        lookup(i);
+    }
+    let per_lookup = start.elapsed().as_nanos() / LOOKUPS as u128;
+    println!("{name:<24} {services:>5} services {per_lookup:>8} ns/lookup");
+}
+
+fn main() {
+    let mut types = Vec::new();
+    for services in [10, 100, 500] {
+        let app = glib::Object::new::<Application>();
+        while types.len() < services {
+            types.push(register_service_type(&format!(
+                "RoseBenchService{}",
+                types.len()
+            )));
+        }
+        for type_ in &types {
+            app.add_dependency_with_type(*type_, &glib::Object::with_type(*type_));
+        }
+
+        measure("exact type", services, |i| {
+            let type_ = types[i as usize % types.len()];
+            black_box(app.try_get_dependency_by_type(type_));
+        });
+        measure("parent type", services, |_| {
+            black_box(app.try_get_dependency_by_type(glib::Object::static_type()));
+        });
+        measure("missing type", services, |_| {
+            black_box(app.try_get_dependency::<gio::Cancellable>());
+        });
+    }
+}
@@ -0,0 +1,143 @@
+use glib::subclass::prelude::*;
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use crate::prelude::*;
+use crate::utils::variant::IsVariantTyVoidExt;
+use crate::{PageRoute, Router};
+
+/// The name of the action that navigates the current window to a route.
+/// Its parameter is a `(sv)` of the route and the route's parameter.
+pub const NAVIGATE_ACTION: &str = "navigate";
+
+/// The name of the action that opens a route in a new window. Its
+/// parameter is the same as `NAVIGATE_ACTION`.
+pub const NAVIGATE_NEW_WINDOW_ACTION: &str = "navigate-new-window";
+
+/// Builds the `(sv)` parameter for the navigate actions. This can be used
+/// as the target of a notification or a desktop file action.
+///
+/// # Example
+/// ```
+/// let target = rose::navigate_target::<ContactRoute>(Some("alice".to_string()));
+/// app.activate_action("navigate", Some(&target));
+/// ```
+pub fn navigate_target<R>(parameter: Option<R::Parameter>) -> glib::Variant
+where
+    R: PageRoute + 'static,
+{
+    let parameter = parameter
+        .map(|parameter| parameter.to_variant())
+        .unwrap_or_else(|| ().to_variant());
+    (R::route(), parameter).to_variant()
+}
+
+/// Adds `app.navigate` and `app.navigate-new-window` so routes can be
+/// opened from outside the application with
+/// `org.freedesktop.Application.ActivateAction`, desktop file actions and
+/// notifications.
+pub(super) fn add_navigation_actions(app: &super::Application) {
+    let variant_type = <(String, glib::Variant)>::static_variant_type();
+
+    let navigate = gio::SimpleAction::new(NAVIGATE_ACTION, Some(&variant_type));
+    let app_ref = app.week_ref();
+    navigate.connect_activate(move |_, parameter| {
+        if let (Some(app), Some(parameter)) = (app_ref.upgrade(), parameter) {
+            navigate_to(&app, parameter, false);
+        }
+    });
+    app.add_action(&navigate);
+
+    let navigate_new_window =
+        gio::SimpleAction::new(NAVIGATE_NEW_WINDOW_ACTION, Some(&variant_type));
+    let app_ref = app.week_ref();
+    navigate_new_window.connect_activate(move |_, parameter| {
+        if let (Some(app), Some(parameter)) = (app_ref.upgrade(), parameter) {
+            navigate_to(&app, parameter, true);
+        }
+    });
+    app.add_action(&navigate_new_window);
+}
+
+fn navigate_to(app: &super::Application, parameter: &glib::Variant, new_window: bool) {
+    if !parameter.is::<(String, glib::Variant)>() {
+        glib::g_warning!(
+            "rose",
+            "Can't navigate with a parameter of type {}, expected (sv)",
+            parameter.type_()
+        );
+        return;
+    }
+    let Some((route, parameter)) = parameter.get::<(String, glib::Variant)>() else {
+        return;
+    };
+    // Routes without a parameter are sent with `()` as their parameter
+    let parameter = Some(parameter).filter(|parameter| !parameter.type_().is_void());
+
+    let router = if new_window {
+        new_window_router(app)
+    } else {
+        // When the action is activated from outside the application there
+        // might not be a window yet so the application is activated to
+        // create one
+        if app.active_window().is_none() {
+            app.activate();
+        }
+        current_router(app)
+    };
+    let Some(router) = router else {
+        glib::g_warning!("rose", "Can't navigate to {route} without a router");
+        return;
+    };
+
+    if !router.has_route(&route) {
+        glib::g_warning!("rose", "Can't navigate to unknown route {route}");
+        return;
+    }
+
+    // The route's action only accepts its own parameter type
+    let imp = crate::router::imp::Router::from_obj(&router);
+    let expected = imp
+        .action_group
+        .action_parameter_type(&format!("visit.{route}"));
+    let received = parameter
+        .as_ref()
+        .map(|parameter| parameter.type_().to_owned());
+    if received != expected {
+        glib::g_warning!(
+            "rose",
+            "Can't navigate to {route} with a parameter of type {}",
+            received.map_or("()".to_string(), |type_| type_.to_string())
+        );
+        return;
+    }
+    unsafe { router.visit_unsafe(&route, parameter) };
+}
+
+/// Gets the router of the active window, or the router added as a
+/// dependency if the active window doesn't have one. Code that navigates
+/// outside a page should use this so it works with more than one window.
+pub(crate) fn current_router(app: &super::Application) -> Option<Router> {
+    let imp = super::imp::Application::from_obj(app);
+    let active_window = app.active_window();
+    imp.routers
+        .borrow()
+        .iter()
+        .find(|(window, _)| window.upgrade().is_some_and(|w| Some(w) == active_window))
+        .and_then(|(_, router)| router.upgrade())
+        .or_else(|| app.try_get_dependency::<Router>())
+}
+
+/// Creates a new window and gets its router. If the application wasn't
+/// made with `ApplicationBuilder` it can't create windows so the current
+/// window is used instead.
+fn new_window_router(app: &super::Application) -> Option<Router> {
+    let imp = super::imp::Application::from_obj(app);
+    match imp.create_window() {
+        Some((window, router)) => {
+            window.present();
+            Some(router)
+        }
+        None => current_router(app),
+    }
+}
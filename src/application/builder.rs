@@ -0,0 +1,249 @@
+use adw::prelude::*;
+use glib::subclass::prelude::*;
+use gtk::{gio, glib};
+use std::rc::Rc;
+
+use super::command_line::OptionValue;
+use crate::prelude::*;
+use crate::{PageRoute, Router};
+
+pub type RouteSetup = Rc<dyn Fn(&Router)>;
+pub type WindowFactory = Rc<dyn Fn(&super::Application) -> adw::ApplicationWindow>;
+pub type ApplicationSetup = Box<dyn FnOnce(&super::Application)>;
+
+/// Builds an `Application` with its routes, dependencies and window. The
+/// dependencies are added when the application starts up and the window
+/// is created with a `Router` as its content when the application is
+/// activated. The window's size and maximized state are saved and
+/// restored with `WindowState`.
+///
+/// # Example
+/// ```
+/// let app = rose::Application::builder()
+///     .application_id("com.example.Contacts")
+///     .dependency(&ContactStore::new())
+///     .main_route::<ContactsRoute>("Contacts", "system-users-symbolic")
+///     .route::<ContactRoute>()
+///     .initial_route::<ContactsRoute>(None)
+///     .window(|app| {
+///         adw::ApplicationWindow::builder()
+///             .application(app)
+///             .default_width(800)
+///             .build()
+///     })
+///     .build();
+/// app.run();
+/// ```
+#[must_use = "The builder must be built to be used"]
+pub struct ApplicationBuilder {
+    application_id: Option<String>,
+    flags: gio::ApplicationFlags,
+    routes: Vec<RouteSetup>,
+    initial_route: Option<RouteSetup>,
+    setup: Vec<ApplicationSetup>,
+    /// Called when the application is built. This is for things that
+    /// need to happen before the application is run like adding options
+    configure: Vec<ApplicationSetup>,
+    window: Option<WindowFactory>,
+    window_role: String,
+}
+
+impl ApplicationBuilder {
+    pub(super) fn new() -> Self {
+        Self {
+            application_id: None,
+            flags: gio::ApplicationFlags::default(),
+            routes: Vec::new(),
+            initial_route: None,
+            setup: Vec::new(),
+            configure: Vec::new(),
+            window: None,
+            window_role: "main".to_string(),
+        }
+    }
+
+    pub fn application_id(mut self, application_id: &str) -> Self {
+        self.application_id = Some(application_id.to_string());
+        self
+    }
+
+    pub fn flags(mut self, flags: gio::ApplicationFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Adds a route to every router the application creates
+    pub fn route<R>(mut self) -> Self
+    where
+        R: PageRoute + 'static,
+    {
+        self.routes.push(Rc::new(|router| router.add_route::<R>()));
+        self
+    }
+
+    /// Adds a main route to every router the application creates. See
+    /// `RoseRouterExt::add_main_route`
+    pub fn main_route<R>(mut self, title: &str, icon: &str) -> Self
+    where
+        R: PageRoute + 'static,
+    {
+        let title = title.to_string();
+        let icon = icon.to_string();
+        self.routes.push(Rc::new(move |router| {
+            router.add_main_route::<R>(&title, &icon)
+        }));
+        self
+    }
+
+    /// Sets the route that is visited when a window is created
+    pub fn initial_route<R>(mut self, parameter: Option<R::Parameter>) -> Self
+    where
+        R: PageRoute + 'static,
+        R::Parameter: Clone,
+    {
+        self.initial_route = Some(Rc::new(move |router| {
+            router.visit::<R>(parameter.clone());
+        }));
+        self
+    }
+
+    /// Adds a dependency when the application starts up
+    pub fn dependency<T>(self, dependency: &T) -> Self
+    where
+        T: IsA<glib::Object>,
+    {
+        let dependency = dependency.clone();
+        self.setup(move |app| app.add_dependency(&dependency))
+    }
+
+    /// Adds a dependency that isn't a GObject when the application starts up
+    pub fn value_dependency<T: 'static>(self, value: T) -> Self {
+        self.setup(move |app| app.add_value_dependency(value))
+    }
+
+    /// Adds a factory for a dependency that is built once when it is first
+    /// requested
+    pub fn singleton<T, F>(self, factory: F) -> Self
+    where
+        T: IsA<glib::Object>,
+        F: Fn(&super::Application) -> T + 'static,
+    {
+        self.setup(move |app| app.add_singleton(factory))
+    }
+
+    /// Calls the callback when the application starts up before any
+    /// window is created. This is useful for setup the builder doesn't
+    /// cover like adding lifecycle hooks.
+    pub fn setup<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(&super::Application) + 'static,
+    {
+        self.setup.push(Box::new(callback));
+        self
+    }
+
+    /// Adds a command line option. See `RoseApplicationExt::add_command_line_option`
+    pub fn command_line_option<T, F>(
+        mut self,
+        long_name: &str,
+        short_name: Option<char>,
+        description: &str,
+        callback: F,
+    ) -> Self
+    where
+        T: OptionValue,
+        F: Fn(&super::Application, T) + 'static,
+    {
+        let long_name = long_name.to_string();
+        let description = description.to_string();
+        self.configure.push(Box::new(move |app| {
+            app.add_command_line_option(&long_name, short_name, &description, callback);
+        }));
+        self
+    }
+
+    /// Adds a command line option that visits `R`. See
+    /// `RoseApplicationExt::add_route_option`
+    pub fn route_option<R>(
+        mut self,
+        long_name: &str,
+        short_name: Option<char>,
+        description: &str,
+    ) -> Self
+    where
+        R: PageRoute + 'static,
+        R::Parameter: OptionValue,
+    {
+        let long_name = long_name.to_string();
+        let description = description.to_string();
+        self.configure.push(Box::new(move |app| {
+            app.add_route_option::<R>(&long_name, short_name, &description);
+        }));
+        self
+    }
+
+    /// Adds a command line option that visits a route by name. See
+    /// `RoseApplicationExt::add_open_route_option`
+    pub fn open_route_option(mut self, long_name: &str, description: &str) -> Self {
+        let long_name = long_name.to_string();
+        let description = description.to_string();
+        self.configure.push(Box::new(move |app| {
+            app.add_open_route_option(&long_name, &description);
+        }));
+        self
+    }
+
+    /// Visits `R` with each file passed on the command line. See
+    /// `RoseApplicationExt::set_files_route`
+    pub fn files_route<R>(mut self) -> Self
+    where
+        R: PageRoute<Parameter = String> + 'static,
+    {
+        self.configure
+            .push(Box::new(|app| app.set_files_route::<R>()));
+        self
+    }
+
+    /// Sets the function that creates the application's windows. The
+    /// router is set as the window's content after it is created. By
+    /// default a plain `adw::ApplicationWindow` is created.
+    pub fn window<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&super::Application) -> adw::ApplicationWindow + 'static,
+    {
+        self.window = Some(Rc::new(factory));
+        self
+    }
+
+    /// Sets the role the window's size and maximized state are saved
+    /// under. The default is `main`. See `WindowState`
+    pub fn window_role(mut self, role: &str) -> Self {
+        self.window_role = role.to_string();
+        self
+    }
+
+    pub fn build(self) -> super::Application {
+        crate::init();
+
+        let mut builder =
+            glib::Object::builder::<super::Application>().property("flags", self.flags);
+        if let Some(application_id) = &self.application_id {
+            builder = builder.property("application-id", application_id);
+        }
+        let app = builder.build();
+
+        let imp = super::imp::Application::from_obj(&app);
+        imp.routes.replace(self.routes);
+        imp.initial_route.replace(self.initial_route);
+        imp.setup.replace(self.setup);
+        for configure in self.configure {
+            configure(&app);
+        }
+        imp.window_role.replace(Some(self.window_role));
+        imp.window_factory
+            .replace(Some(self.window.unwrap_or_else(|| {
+                Rc::new(|app| adw::ApplicationWindow::builder().application(app).build())
+            })));
+        app
+    }
+}
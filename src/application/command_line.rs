@@ -0,0 +1,58 @@
+use gtk::glib::{self, OptionArg, VariantDict};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// A value that can be passed to the application as a command line option
+pub trait OptionValue: Sized + 'static {
+    /// The kind of argument the option takes
+    const ARG: OptionArg;
+
+    /// Gets the value of the option from the parsed options
+    fn from_options(options: &VariantDict, name: &str) -> Option<Self>;
+}
+
+macro_rules! impl_option_value {
+    ($type:ty, $arg:expr) => {
+        impl OptionValue for $type {
+            const ARG: OptionArg = $arg;
+
+            fn from_options(options: &VariantDict, name: &str) -> Option<Self> {
+                options.lookup::<$type>(name).ok().flatten()
+            }
+        }
+    };
+}
+
+impl_option_value!(bool, OptionArg::None);
+impl_option_value!(String, OptionArg::String);
+impl_option_value!(i32, OptionArg::Int);
+impl_option_value!(i64, OptionArg::Int64);
+impl_option_value!(f64, OptionArg::Double);
+impl_option_value!(PathBuf, OptionArg::Filename);
+impl_option_value!(Vec<String>, OptionArg::StringArray);
+
+/// `()` is a flag that takes no argument. This lets routes without a
+/// parameter be opened with an option like `--settings`
+impl OptionValue for () {
+    const ARG: OptionArg = OptionArg::None;
+
+    fn from_options(options: &VariantDict, name: &str) -> Option<Self> {
+        options
+            .lookup::<bool>(name)
+            .ok()
+            .flatten()
+            .filter(|set| *set)
+            .map(|_| ())
+    }
+}
+
+pub type OptionHandler = Rc<dyn Fn(&super::Application, &VariantDict)>;
+pub type FilesHandler = Rc<dyn Fn(&super::Application, Vec<gtk::gio::File>)>;
+
+/// Converts a char to the short name of an option. `None` means the
+/// option has no short name
+pub(crate) fn short_name(short_name: Option<char>) -> glib::Char {
+    short_name
+        .and_then(|short_name| glib::Char::try_from(short_name).ok())
+        .unwrap_or(glib::Char(0))
+}
@@ -0,0 +1,226 @@
+use gtk::glib::{self, IsA};
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// How long a dependency that is built by a factory lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyLifetime {
+    /// The factory is called the first time the dependency is requested
+    /// and the result is reused for every request after that
+    Singleton,
+    /// The factory is called every time the dependency is requested
+    Transient,
+}
+
+type FactoryFn = dyn Fn(&super::Application) -> glib::Object;
+
+/// A registered factory for a dependency
+#[derive(Clone)]
+pub struct DependencyFactory {
+    pub type_: glib::Type,
+    pub lifetime: DependencyLifetime,
+    build: Rc<FactoryFn>,
+}
+
+impl DependencyFactory {
+    pub fn new<T, F>(lifetime: DependencyLifetime, factory: F) -> Self
+    where
+        T: IsA<glib::Object>,
+        F: Fn(&super::Application) -> T + 'static,
+    {
+        Self {
+            type_: T::static_type(),
+            lifetime,
+            build: Rc::new(move |app| factory(app).upcast()),
+        }
+    }
+
+    pub fn build(&self, app: &super::Application) -> glib::Object {
+        (self.build)(app)
+    }
+}
+
+impl std::fmt::Debug for DependencyFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DependencyFactory")
+            .field("type_", &self.type_)
+            .field("lifetime", &self.lifetime)
+            .finish()
+    }
+}
+
+/// Factories that depend on each other in a cycle. This holds the chain
+/// of types that were being built.
+#[derive(Debug, Clone)]
+pub struct DependencyCycle(pub Vec<glib::Type>);
+
+impl std::fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chain = self
+            .0
+            .iter()
+            .map(|type_| type_.name())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        write!(f, "Dependency cycle detected: {chain}")
+    }
+}
+
+/// A dependency that has been added to the application
+#[derive(Debug, Clone)]
+pub enum Dependency {
+    Instance(glib::Object),
+    Factory(DependencyFactory),
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// When the dependency was added. This is used to pick a dependency
+    /// when more than one could be used for a type
+    order: u64,
+    dependency: Dependency,
+}
+
+/// Stores dependencies keyed by the type they were registered as.
+///
+/// Dependencies are resolved with the following rules:
+/// 1. A dependency registered as the requested type is always used.
+/// 2. Otherwise a dependency registered as a subtype of the requested
+///    type is used. When there is more than one the one that was added
+///    first wins.
+/// 3. A dependency registered as a parent type of the requested type is
+///    never used even if the object itself is the requested type.
+///
+/// Resolving rule 2 requires looking at every dependency so the result is
+/// cached per requested type until a dependency is added or removed.
+#[derive(Debug, Default)]
+pub struct DependencyStore {
+    entries: HashMap<glib::Type, Entry>,
+    resolved: RefCell<HashMap<glib::Type, Option<glib::Type>>>,
+    next_order: u64,
+}
+
+impl DependencyStore {
+    /// Finds the type the dependency for `type_` was registered as
+    pub fn resolve(&self, type_: glib::Type) -> Option<glib::Type> {
+        if self.entries.contains_key(&type_) {
+            return Some(type_);
+        }
+        if let Some(key) = self.resolved.borrow().get(&type_) {
+            return *key;
+        }
+
+        let key = self
+            .entries
+            .iter()
+            .filter(|(key, _)| key.is_a(type_))
+            .min_by_key(|(_, entry)| entry.order)
+            .map(|(key, _)| *key);
+        self.resolved.borrow_mut().insert(type_, key);
+        key
+    }
+
+    pub fn get(&self, type_: glib::Type) -> Option<&Dependency> {
+        self.resolve(type_)
+            .and_then(|key| self.entries.get(&key))
+            .map(|entry| &entry.dependency)
+    }
+
+    /// Checks if a dependency was registered as exactly `type_`
+    pub fn contains_key(&self, type_: glib::Type) -> bool {
+        self.entries.contains_key(&type_)
+    }
+
+    pub fn insert(&mut self, type_: glib::Type, dependency: Dependency) {
+        let order = self.next_order;
+        self.next_order += 1;
+        self.entries.insert(type_, Entry { order, dependency });
+        self.resolved.borrow_mut().clear();
+    }
+
+    /// Replaces a singleton's factory with the dependency it built
+    pub fn set_instance(&mut self, type_: glib::Type, dependency: glib::Object) {
+        if let Some(entry) = self.entries.get_mut(&type_) {
+            entry.dependency = Dependency::Instance(dependency);
+        }
+    }
+
+    pub fn remove(&mut self, type_: glib::Type) -> Option<Dependency> {
+        let key = self.resolve(type_)?;
+        self.resolved.borrow_mut().clear();
+        self.entries.remove(&key).map(|entry| entry.dependency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gtk::gio;
+
+    fn store(types: &[glib::Type]) -> DependencyStore {
+        let mut store = DependencyStore::default();
+        for type_ in types {
+            store.insert(
+                *type_,
+                Dependency::Instance(glib::Object::new::<glib::Object>()),
+            );
+        }
+        store
+    }
+
+    #[test]
+    fn exact_match_is_used_over_subtypes() {
+        let store = store(&[gio::Menu::static_type(), glib::Type::OBJECT]);
+        assert_eq!(store.resolve(glib::Type::OBJECT), Some(glib::Type::OBJECT));
+    }
+
+    #[test]
+    fn subtype_is_used_for_parent_request() {
+        let store = store(&[gio::Menu::static_type()]);
+        assert_eq!(
+            store.resolve(gio::MenuModel::static_type()),
+            Some(gio::Menu::static_type())
+        );
+        assert_eq!(
+            store.resolve(glib::Type::OBJECT),
+            Some(gio::Menu::static_type())
+        );
+    }
+
+    #[test]
+    fn first_added_subtype_wins() {
+        let store = store(&[gio::DBusMenuModel::static_type(), gio::Menu::static_type()]);
+        assert_eq!(
+            store.resolve(gio::MenuModel::static_type()),
+            Some(gio::DBusMenuModel::static_type())
+        );
+    }
+
+    #[test]
+    fn parent_type_never_matches() {
+        let store = store(&[glib::Type::OBJECT, gio::MenuModel::static_type()]);
+        assert_eq!(store.resolve(gio::Menu::static_type()), None);
+        assert!(store.get(gio::Menu::static_type()).is_none());
+    }
+
+    #[test]
+    fn insert_and_remove_clear_the_cache() {
+        let mut store = store(&[]);
+        assert_eq!(store.resolve(gio::MenuModel::static_type()), None);
+
+        store.insert(
+            gio::Menu::static_type(),
+            Dependency::Instance(glib::Object::new::<glib::Object>()),
+        );
+        assert_eq!(
+            store.resolve(gio::MenuModel::static_type()),
+            Some(gio::Menu::static_type())
+        );
+
+        assert!(store.remove(gio::MenuModel::static_type()).is_some());
+        assert_eq!(store.resolve(gio::MenuModel::static_type()), None);
+        assert!(!store.contains_key(gio::Menu::static_type()));
+    }
+}
@@ -3,6 +3,17 @@ use glib::IsA;
 use gtk::glib::Cast;
 use gtk::prelude::*;
 use gtk::{gio, glib};
+use std::any::{type_name, TypeId};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::prelude::*;
+use crate::{EventBus, FailureReporter, PageRoute, TypedSettings};
+
+use super::command_line::{self, OptionValue};
+use super::dependency::{Dependency, DependencyFactory, DependencyLifetime};
+use super::lifecycle::{Lifecycle, LifecycleHook};
+use super::notification::{DesktopNotificationBackend, NotificationBackend, RouteNotification};
 
 pub trait RoseApplicationExt:
     glib::object::IsClass
@@ -27,37 +38,175 @@ pub trait RoseApplicationExt:
     where
         T: IsA<glib::Object>,
     {
-        if self.has_dependency::<T>() {
+        self.add_dependency_with_type(T::static_type(), dependency.upcast_ref());
+    }
+
+    /// Add a dependency that is looked up by the interface or parent
+    /// class `I` instead of its own type. This lets code depend on an
+    /// interface while the implementation is swapped out for tests.
+    ///
+    /// # Example
+    /// ```
+    /// app.add_dependency_as::<Storage, _>(&FileStorage::new());
+    /// let storage = app.get_dependency::<Storage>();
+    /// ```
+    fn add_dependency_as<I, T>(&self, dependency: &T)
+    where
+        I: IsA<glib::Object>,
+        T: IsA<I> + IsA<glib::Object>,
+    {
+        self.add_dependency_with_type(
+            I::static_type(),
+            dependency.upcast_ref::<glib::Object>(),
+        );
+    }
+
+    /// Add a dependency that is looked up by `type_`. This is useful when
+    /// the type is only known at runtime like a GType from a plugin.
+    fn add_dependency_with_type(&self, type_: glib::Type, dependency: &glib::Object) {
+        if !dependency.type_().is_a(type_) {
+            panic!(
+                "Can't add a dependency of type {} as {}",
+                dependency.type_().name(),
+                type_.name()
+            );
+        }
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let mut dependencies = this.dependencies.borrow_mut();
+        if dependencies.contains_key(type_) {
+            panic!("The dependency of type {} is already added", type_.name());
+        }
+        dependencies.insert(type_, Dependency::Instance(dependency.clone()));
+    }
+
+    /// Replaces the dependency of type `T` returning the old one if it had
+    /// been built. This is useful for overriding services in tests after
+    /// the application has started.
+    ///
+    /// `T` is inferred from the argument so this replaces the dependency
+    /// that was added as the argument's own type. A dependency added with
+    /// `add_dependency_as` has to be replaced with `replace_dependency_as`
+    /// or the old one stays registered as the interface.
+    fn replace_dependency<T>(&self, dependency: &T) -> Option<T>
+    where
+        T: IsA<glib::Object>,
+    {
+        let old = self.remove_dependency::<T>();
+        self.add_dependency(dependency);
+        old
+    }
+
+    /// Replaces the dependency that was added as the interface or parent
+    /// class `I` returning the old one if it had been built
+    ///
+    /// # Example
+    /// ```
+    /// app.add_dependency_as::<Storage, _>(&FileStorage::new());
+    /// app.replace_dependency_as::<Storage, _>(&MemoryStorage::new());
+    /// ```
+    fn replace_dependency_as<I, T>(&self, dependency: &T) -> Option<I>
+    where
+        I: IsA<glib::Object>,
+        T: IsA<I> + IsA<glib::Object>,
+    {
+        let old = self.remove_dependency::<I>();
+        self.add_dependency_as::<I, T>(dependency);
+        old
+    }
+
+    /// Removes the dependency and the factory of type `T` returning the
+    /// dependency if it had been built
+    fn remove_dependency<T>(&self) -> Option<T>
+    where
+        T: IsA<glib::Object>,
+    {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.remove_dependency(T::static_type())
+            .map(|d| d.downcast().expect("Failed to downcast dependency"))
+    }
+
+    /// Add a factory that builds a dependency the first time it is
+    /// requested. The factory is given the application so it can get its
+    /// own dependencies. Singletons are only built once while transient
+    /// dependencies are built every time they are requested.
+    ///
+    /// # Example
+    /// ```
+    /// app.add_factory(DependencyLifetime::Singleton, |app| {
+    ///     ContactStore::new(&app.get_dependency::<Database>())
+    /// });
+    /// ```
+    fn add_factory<T, F>(&self, lifetime: DependencyLifetime, factory: F)
+    where
+        T: IsA<glib::Object>,
+        F: Fn(&super::Application) -> T + 'static,
+    {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let mut dependencies = this.dependencies.borrow_mut();
+        if dependencies.contains_key(T::static_type()) {
             panic!(
                 "The dependency of type {} is already added",
                 T::static_type().name()
             );
         }
-        let this = super::imp::Application::from_obj(self.upcast_ref());
-        this.dependencies
-            .borrow_mut()
-            .push(dependency.clone().upcast());
+        dependencies.insert(
+            T::static_type(),
+            Dependency::Factory(DependencyFactory::new(lifetime, factory)),
+        );
     }
 
-    /// Get a dependency from the application
+    /// Add a factory for a dependency that is built once the first time
+    /// it is requested
+    fn add_singleton<T, F>(&self, factory: F)
+    where
+        T: IsA<glib::Object>,
+        F: Fn(&super::Application) -> T + 'static,
+    {
+        self.add_factory(DependencyLifetime::Singleton, factory);
+    }
+
+    /// Add a factory for a dependency that is built every time it is
+    /// requested
+    fn add_transient<T, F>(&self, factory: F)
+    where
+        T: IsA<glib::Object>,
+        F: Fn(&super::Application) -> T + 'static,
+    {
+        self.add_factory(DependencyLifetime::Transient, factory);
+    }
+
+    /// Get a dependency from the application. This panics if there is no
+    /// dependency or if its factories depend on each other in a cycle.
     fn get_dependency<T>(&self) -> T
     where
         T: IsA<glib::Object>,
     {
-        self.try_get_dependency().expect(&format!(
-            "Failed to get dependency {}",
-            T::static_type().name()
-        ))
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let dependency = this
+            .lookup_dependency(T::static_type())
+            .unwrap_or_else(|cycle| panic!("{cycle}"));
+        dependency
+            .map(|d| d.downcast().expect("Failed to downcast dependency"))
+            .unwrap_or_else(|| panic!("Failed to get dependency {}", T::static_type().name()))
     }
 
-    /// Try's to get a dependency from the application
+    /// Try's to get a dependency from the application. If the dependency
+    /// hasn't been added but a factory for it has the factory will be
+    /// called to build it. If the factories depend on each other in a
+    /// cycle this logs the chain of types and returns `None`.
     fn try_get_dependency<T: IsA<glib::Object>>(&self) -> Option<T> {
+        self.try_get_dependency_by_type(T::static_type())
+            .map(|d| d.downcast().expect("Failed to downcast dependency"))
+    }
+
+    /// Try's to get the dependency that was registered as `type_` or as
+    /// a subtype of `type_`
+    fn try_get_dependency_by_type(&self, type_: glib::Type) -> Option<glib::Object> {
         let this = super::imp::Application::from_obj(self.upcast_ref());
-        let dependencies = this.dependencies.borrow();
-        dependencies
-            .iter()
-            .find(|d| d.is::<T>())
-            .map(|d| d.clone().downcast().expect("Failed to downcast dependency"))
+        this.lookup_dependency(type_).unwrap_or_else(|cycle| {
+            glib::g_warning!("rose", "{cycle}");
+            None
+        })
     }
 
     /// Checks if the application has a dependency
@@ -66,8 +215,368 @@ pub trait RoseApplicationExt:
         T: IsA<glib::Object>,
     {
         let this = super::imp::Application::from_obj(self.upcast_ref());
-        let dependencies = this.dependencies.borrow();
-        dependencies.iter().any(|d| d.is::<T>())
+        this.contains_dependency(T::static_type())
+    }
+
+    /// Add a dependency that is looked up by name. This is useful when
+    /// more than one dependency of the same type is needed. Named
+    /// dependencies are separate from the dependencies added with
+    /// `add_dependency` so they are never returned by `get_dependency`.
+    ///
+    /// # Example
+    /// ```
+    /// app.add_dependency_named("primary", &gio::Settings::new("com.example.App"));
+    /// app.add_dependency_named("sync", &gio::Settings::new("com.example.App.Sync"));
+    ///
+    /// let settings = app.get_dependency_named::<gio::Settings>("sync");
+    /// ```
+    fn add_dependency_named<T>(&self, name: &str, dependency: &T)
+    where
+        T: IsA<glib::Object>,
+    {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let mut named = this.named.borrow_mut();
+        if named.contains_key(name) {
+            panic!("The dependency named {name} is already added");
+        }
+        named.insert(name.to_string(), dependency.clone().upcast());
+    }
+
+    /// Get a dependency by its name
+    fn get_dependency_named<T>(&self, name: &str) -> T
+    where
+        T: IsA<glib::Object>,
+    {
+        self.try_get_dependency_named(name).unwrap_or_else(|| {
+            panic!(
+                "Failed to get dependency {name} of type {}",
+                T::static_type().name()
+            )
+        })
+    }
+
+    /// Try's to get a dependency by its name. This returns `None` if there
+    /// is no dependency with the name or if it isn't a `T`
+    fn try_get_dependency_named<T>(&self, name: &str) -> Option<T>
+    where
+        T: IsA<glib::Object>,
+    {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let named = this.named.borrow();
+        named
+            .get(name)
+            .and_then(|d| d.clone().downcast::<T>().ok())
+    }
+
+    /// Checks if the application has a dependency with the name that is a `T`
+    fn has_dependency_named<T>(&self, name: &str) -> bool
+    where
+        T: IsA<glib::Object>,
+    {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let named = this.named.borrow();
+        named.get(name).is_some_and(|d| d.is::<T>())
+    }
+
+    /// Removes the dependency with the name returning it
+    fn remove_dependency_named(&self, name: &str) -> Option<glib::Object> {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let mut named = this.named.borrow_mut();
+        named.remove(name)
+    }
+
+    /// Add a dependency that isn't a GObject. This is useful for plain
+    /// rust types like a database pool or a config. The value is shared
+    /// as a `Rc<T>` when it is requested.
+    fn add_value_dependency<T: 'static>(&self, value: T) {
+        self.add_shared_value_dependency(Rc::new(value));
+    }
+
+    /// Add a dependency that is already shared. `T` can be a trait object
+    /// which lets code depend on a trait instead of a concrete type.
+    ///
+    /// # Example
+    /// ```
+    /// app.add_shared_value_dependency::<dyn Storage>(Rc::new(FileStorage::new()));
+    /// let storage = app.get_value_dependency::<dyn Storage>();
+    /// ```
+    fn add_shared_value_dependency<T: ?Sized + 'static>(&self, value: Rc<T>) {
+        if self.has_value_dependency::<T>() {
+            panic!("The dependency of type {} is already added", type_name::<T>());
+        }
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.values
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Get a dependency that isn't a GObject from the application
+    fn get_value_dependency<T: ?Sized + 'static>(&self) -> Rc<T> {
+        self.try_get_value_dependency().unwrap_or_else(|| {
+            panic!("Failed to get dependency {}", type_name::<T>())
+        })
+    }
+
+    /// Try's to get a dependency that isn't a GObject from the application
+    fn try_get_value_dependency<T: ?Sized + 'static>(&self) -> Option<Rc<T>> {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let values = this.values.borrow();
+        values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<Rc<T>>())
+            .cloned()
+    }
+
+    /// Checks if the application has a dependency that isn't a GObject
+    fn has_value_dependency<T: ?Sized + 'static>(&self) -> bool {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let values = this.values.borrow();
+        values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Replaces the dependency of type `T` returning the old one
+    fn replace_value_dependency<T: ?Sized + 'static>(&self, value: Rc<T>) -> Option<Rc<T>> {
+        let old = self.remove_value_dependency::<T>();
+        self.add_shared_value_dependency(value);
+        old
+    }
+
+    /// Removes the dependency of type `T` returning it
+    fn remove_value_dependency<T: ?Sized + 'static>(&self) -> Option<Rc<T>> {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let mut values = this.values.borrow_mut();
+        values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast::<Rc<T>>().ok())
+            .map(|value| *value)
+    }
+
+    /// Add hooks that are called when the application starts up and
+    /// shuts down. Hooks are started in the order they are added and shut
+    /// down in reverse. If the application has already started `on_start`
+    /// is called right away.
+    fn add_lifecycle_hook<L: Lifecycle>(&self, hook: L) {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.add_lifecycle_hook(LifecycleHook {
+            hook: Rc::new(hook),
+            type_: None,
+        });
+    }
+
+    /// Add a dependency and its lifecycle hooks. Its hooks are shut down
+    /// after the hooks of the managed dependencies whose factories used it.
+    fn add_managed_dependency<T>(&self, dependency: &T)
+    where
+        T: IsA<glib::Object> + Lifecycle,
+    {
+        self.add_dependency(dependency);
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.add_lifecycle_hook(LifecycleHook {
+            hook: Rc::new(dependency.clone()),
+            type_: Some(dependency.type_()),
+        });
+    }
+
+    /// Add a factory for a dependency that is built once and whose
+    /// lifecycle hooks are added when it is built. Since a factory builds
+    /// its own dependencies first they are always started before it.
+    fn add_managed_singleton<T, F>(&self, factory: F)
+    where
+        T: IsA<glib::Object> + Lifecycle,
+        F: Fn(&super::Application) -> T + 'static,
+    {
+        self.add_singleton(move |app| {
+            let dependency = factory(app);
+            let this = super::imp::Application::from_obj(app);
+            this.add_lifecycle_hook(LifecycleHook {
+                hook: Rc::new(dependency.clone()),
+                type_: Some(dependency.type_()),
+            });
+            dependency
+        });
+    }
+
+    /// Add a dependency that isn't a GObject and its lifecycle hooks
+    fn add_managed_value_dependency<T: Lifecycle>(&self, value: T) {
+        let value = Rc::new(value);
+        self.add_shared_value_dependency(value.clone());
+        self.add_lifecycle_hook(value);
+    }
+
+    /// Sets how long the application waits for each shutdown hook and then
+    /// for running tasks before giving up. The default is 5 seconds.
+    fn set_shutdown_timeout(&self, timeout: Duration) {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.shutdown_timeout.set(Some(timeout));
+    }
+
+    /// Gets how long the application waits for shutdown hooks
+    fn shutdown_timeout(&self) -> Duration {
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.shutdown_timeout
+            .get()
+            .unwrap_or(super::imp::DEFAULT_SHUTDOWN_TIMEOUT)
+    }
+
+    /// Adds a command line option. When the option is passed the callback
+    /// is called with its value in the primary instance, so running the
+    /// app again while it is open will call the callback in the instance
+    /// that is already running. Options must be added before the
+    /// application is run.
+    ///
+    /// # Example
+    /// ```
+    /// app.add_command_line_option::<String, _>("query", Some('q'), "Search for a contact", |app, query| {
+    ///     app.get_dependency::<ContactStore>().search(&query);
+    /// });
+    /// ```
+    fn add_command_line_option<T, F>(
+        &self,
+        long_name: &str,
+        short_name: Option<char>,
+        description: &str,
+        callback: F,
+    ) where
+        T: OptionValue,
+        F: Fn(&super::Application, T) + 'static,
+    {
+        self.add_main_option(
+            long_name,
+            command_line::short_name(short_name),
+            glib::OptionFlags::NONE,
+            T::ARG,
+            description,
+            None,
+        );
+        self.set_flags(self.flags() | gio::ApplicationFlags::HANDLES_COMMAND_LINE);
+
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        let long_name = long_name.to_string();
+        this.command_line_options
+            .borrow_mut()
+            .push(Rc::new(move |app, options| {
+                if let Some(value) = T::from_options(options, &long_name) {
+                    callback(app, value);
+                }
+            }));
+    }
+
+    /// Adds a command line option that visits `R` with the option's value
+    /// as its parameter. Routes that take `()` are visited when the option
+    /// is passed as a flag.
+    fn add_route_option<R>(&self, long_name: &str, short_name: Option<char>, description: &str)
+    where
+        R: PageRoute + 'static,
+        R::Parameter: OptionValue,
+    {
+        self.add_command_line_option::<R::Parameter, _>(
+            long_name,
+            short_name,
+            description,
+            |app, parameter| match super::actions::current_router(app) {
+                Some(router) => router.visit::<R>(Some(parameter)),
+                None => glib::g_warning!("rose", "Can't visit {} without a router", R::route()),
+            },
+        );
+    }
+
+    /// Adds a command line option that takes the name of a route and
+    /// visits it like `--open-route=settings`. Only routes without a
+    /// parameter can be opened this way.
+    fn add_open_route_option(&self, long_name: &str, description: &str) {
+        self.add_command_line_option::<String, _>(long_name, None, description, |app, route| {
+            let Some(router) = super::actions::current_router(app) else {
+                glib::g_warning!("rose", "Can't open {route} without a router");
+                return;
+            };
+            if router.has_route(&route) {
+                unsafe { router.visit_unsafe(&route, None) };
+            } else {
+                glib::g_warning!("rose", "Can't open unknown route {route}");
+            }
+        });
+    }
+
+    /// Calls the callback with the files passed on the command line like
+    /// `myapp file.txt`. Like options the callback is called in the
+    /// primary instance.
+    fn set_files_handler<F>(&self, callback: F)
+    where
+        F: Fn(&super::Application, Vec<gio::File>) + 'static,
+    {
+        self.set_flags(self.flags() | gio::ApplicationFlags::HANDLES_COMMAND_LINE);
+
+        let this = super::imp::Application::from_obj(self.upcast_ref());
+        this.files_handler.replace(Some(Rc::new(callback)));
+    }
+
+    /// Visits `R` with the uri of each file passed on the command line
+    fn set_files_route<R>(&self)
+    where
+        R: PageRoute<Parameter = String> + 'static,
+    {
+        self.set_files_handler(|app, files| {
+            let Some(router) = super::actions::current_router(app) else {
+                glib::g_warning!("rose", "Can't open files without a router");
+                return;
+            };
+            for file in files {
+                router.visit::<R>(Some(file.uri().to_string()));
+            }
+        });
+    }
+
+    /// Sends a notification that opens a route when it is clicked. If a
+    /// `dyn NotificationBackend` has been added as a dependency it is used
+    /// to send the notification, otherwise it is sent to the desktop.
+    fn send_route_notification(&self, id: Option<&str>, notification: &RouteNotification) {
+        match self.try_get_value_dependency::<dyn NotificationBackend>() {
+            Some(backend) => backend.send(self.upcast_ref(), id, notification),
+            None => DesktopNotificationBackend.send(self.upcast_ref(), id, notification),
+        }
+    }
+
+    /// Withdraws a notification sent with `send_route_notification`
+    fn withdraw_route_notification(&self, id: &str) {
+        match self.try_get_value_dependency::<dyn NotificationBackend>() {
+            Some(backend) => backend.withdraw(self.upcast_ref(), id),
+            None => DesktopNotificationBackend.withdraw(self.upcast_ref(), id),
+        }
+    }
+
+    /// Adds typed settings as a dependency. The settings can be gotten
+    /// with `get_value_dependency::<S>()` and the `gio::Settings` they wrap
+    /// with `get_dependency_named::<gio::Settings>(S::SCHEMA_ID)`.
+    fn add_settings<S: TypedSettings>(&self, settings: S) {
+        self.add_dependency_named(S::SCHEMA_ID, settings.settings());
+        self.add_value_dependency(settings);
+    }
+
+    /// Sets what happens when a spawned task or a callback panics. By
+    /// default the router's error page is shown.
+    ///
+    /// # Example
+    /// ```
+    /// app.set_failure_reporter(rose::CrashDialogReporter);
+    /// ```
+    fn set_failure_reporter<R: FailureReporter>(&self, reporter: R) {
+        self.replace_value_dependency::<dyn FailureReporter>(Rc::new(reporter));
+    }
+
+    /// Gets the application's event bus. It's added as a value dependency
+    /// the first time it's used.
+    ///
+    /// # Example
+    /// ```
+    /// app.event_bus().publish(SyncFinished);
+    /// ```
+    fn event_bus(&self) -> Rc<EventBus> {
+        if let Some(bus) = self.try_get_value_dependency::<EventBus>() {
+            return bus;
+        }
+        let bus = Rc::new(EventBus::new());
+        self.add_shared_value_dependency(bus.clone());
+        bus
     }
 }
 
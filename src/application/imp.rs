@@ -1,11 +1,178 @@
+use super::builder::{ApplicationSetup, RouteSetup, WindowFactory};
+use super::command_line::{FilesHandler, OptionHandler};
+use super::dependency::{
+    Dependency, DependencyCycle, DependencyFactory, DependencyLifetime, DependencyStore,
+};
+use super::lifecycle::{shutdown_order, LifecycleHook};
 use super::subclass::RoseApplicationImpl;
+use super::window_state::WindowState;
+use crate::prelude::*;
+use crate::Router;
+use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gtk::glib;
-use std::cell::RefCell;
+use gtk::{gio, glib};
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::Duration;
 
-#[derive(Default, Debug)]
+/// How long the application waits for shutdown hooks by default
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
 pub struct Application {
-    pub dependencies: RefCell<Vec<glib::Object>>,
+    /// Dependencies and factories keyed by the type they were registered
+    /// as. The type can be an interface or a parent class of the dependency
+    pub dependencies: RefCell<DependencyStore>,
+    /// Dependencies that are looked up by name instead of by type. This
+    /// allows more than one dependency of the same type
+    pub named: RefCell<HashMap<String, glib::Object>>,
+    /// Dependencies that aren't GObjects. Each value is a `Rc<T>` boxed
+    /// as `Any` and keyed by the `TypeId` of `T`
+    pub values: RefCell<HashMap<TypeId, Box<dyn Any>>>,
+    /// The types of the factories that are currently being called. This
+    /// is used to detect dependency cycles
+    pub resolving: RefCell<Vec<glib::Type>>,
+    /// Lifecycle hooks in the order they were added
+    pub lifecycle: RefCell<Vec<LifecycleHook>>,
+    /// The dependencies factories got while they were building. Each
+    /// entry is the type being built and the type of the dependency it
+    /// got. This is used to order the shutdown hooks
+    pub uses: RefCell<Vec<(glib::Type, glib::Type)>>,
+    pub started: Cell<bool>,
+    pub shutdown_timeout: Cell<Option<Duration>>,
+    /// Set by `ApplicationBuilder`
+    pub routes: RefCell<Vec<RouteSetup>>,
+    pub initial_route: RefCell<Option<RouteSetup>>,
+    pub setup: RefCell<Vec<ApplicationSetup>>,
+    pub window_factory: RefCell<Option<WindowFactory>>,
+    pub window_role: RefCell<Option<String>>,
+    /// Handlers for the options added with `add_command_line_option`
+    pub command_line_options: RefCell<Vec<OptionHandler>>,
+    pub files_handler: RefCell<Option<FilesHandler>>,
+    /// The routers of the windows made by `create_window`
+    pub routers: RefCell<Vec<(glib::WeakRef<gtk::Window>, glib::WeakRef<Router>)>>,
+}
+
+impl Application {
+    /// Finds the dependency for `type_` building it if it was added as
+    /// a factory. See `DependencyStore` for how the type is resolved.
+    pub fn lookup_dependency(
+        &self,
+        type_: glib::Type,
+    ) -> Result<Option<glib::Object>, DependencyCycle> {
+        let dependency = self.dependencies.borrow().get(type_).cloned();
+        let dependency = match dependency {
+            Some(Dependency::Instance(dependency)) => dependency,
+            Some(Dependency::Factory(factory)) => self.build_dependency(&factory)?,
+            None => return Ok(None),
+        };
+
+        if let Some(user) = self.resolving.borrow().last() {
+            let used = (*user, dependency.type_());
+            let mut uses = self.uses.borrow_mut();
+            if !uses.contains(&used) {
+                uses.push(used);
+            }
+        }
+        Ok(Some(dependency))
+    }
+
+    /// Adds a lifecycle hook calling `on_start` right away if the
+    /// application has already started
+    pub fn add_lifecycle_hook(&self, hook: LifecycleHook) {
+        self.lifecycle.borrow_mut().push(hook.clone());
+        if self.started.get() {
+            hook.hook.on_start(self.obj().upcast_ref());
+        }
+    }
+
+    /// Checks if there is a dependency or a factory for `type_`
+    pub fn contains_dependency(&self, type_: glib::Type) -> bool {
+        self.dependencies.borrow().resolve(type_).is_some()
+    }
+
+    /// Removes the dependency or the factory for `type_` returning the
+    /// dependency if it had been built
+    pub fn remove_dependency(&self, type_: glib::Type) -> Option<glib::Object> {
+        match self.dependencies.borrow_mut().remove(type_)? {
+            Dependency::Instance(dependency) => Some(dependency),
+            Dependency::Factory(_) => None,
+        }
+    }
+
+    /// Creates a window with a new router as its content using the
+    /// window factory and routes from `ApplicationBuilder`. This returns
+    /// `None` if the application wasn't made with the builder.
+    pub fn create_window(&self) -> Option<(adw::ApplicationWindow, Router)> {
+        let factory = self.window_factory.borrow().clone()?;
+        let app = self.obj();
+
+        let router = glib::Object::new::<Router>();
+        router.set_child(Some(&router.view()));
+        for route in self.routes.borrow().iter() {
+            route(&router);
+        }
+
+        let window = factory(&app);
+        if window.application().is_none() {
+            window.set_application(Some(&*app));
+        }
+        window.set_content(Some(&router));
+        if let (Some(application_id), Some(role)) =
+            (app.application_id(), self.window_role.borrow().as_deref())
+        {
+            WindowState::attach(&window, &application_id, role);
+        }
+
+        if let Some(initial_route) = self.initial_route.borrow().clone() {
+            initial_route(&router);
+        }
+
+        let mut routers = self.routers.borrow_mut();
+        routers.retain(|(window, _)| window.upgrade().is_some());
+        routers.push((
+            window.upcast_ref::<gtk::Window>().week_ref(),
+            router.week_ref(),
+        ));
+        Some((window, router))
+    }
+
+    /// Builds a dependency with its factory. Singletons replace their
+    /// factory with the dependency so the factory is only called once.
+    pub fn build_dependency(
+        &self,
+        factory: &DependencyFactory,
+    ) -> Result<glib::Object, DependencyCycle> {
+        if self.resolving.borrow().contains(&factory.type_) {
+            let mut chain = self.resolving.borrow().clone();
+            chain.push(factory.type_);
+            return Err(DependencyCycle(chain));
+        }
+
+        self.resolving.borrow_mut().push(factory.type_);
+        let dependency = {
+            let _guard = ResolvingGuard(&self.resolving);
+            factory.build(&self.obj())
+        };
+
+        if factory.lifetime == DependencyLifetime::Singleton {
+            self.dependencies
+                .borrow_mut()
+                .set_instance(factory.type_, dependency.clone());
+        }
+        Ok(dependency)
+    }
+}
+
+/// Pops the type that is being built from `resolving` even if its factory
+/// panics
+struct ResolvingGuard<'a>(&'a RefCell<Vec<glib::Type>>);
+
+impl Drop for ResolvingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.borrow_mut().pop();
+    }
 }
 
 #[glib::object_subclass]
@@ -18,9 +185,132 @@ impl ObjectSubclass for Application {
 impl ObjectImpl for Application {
     fn constructed(&self) {
         self.parent_constructed();
+        super::actions::add_navigation_actions(&self.obj());
+    }
+}
+impl ApplicationImpl for Application {
+    fn startup(&self) {
+        self.parent_startup();
+        crate::runtime::start();
+
+        let setup = self.setup.take();
+        for setup in setup {
+            setup(&self.obj());
+        }
+        self.started.set(true);
+
+        let hooks = self.lifecycle.borrow().clone();
+        for hook in hooks {
+            hook.hook.on_start(&self.obj());
+        }
+    }
+
+    fn activate(&self) {
+        self.parent_activate();
+
+        if let Some(window) = self.obj().active_window() {
+            window.present();
+        } else if let Some((window, _)) = self.create_window() {
+            window.present();
+        }
+    }
+
+    fn command_line(&self, command_line: &gio::ApplicationCommandLine) -> glib::ExitCode {
+        // This runs in the primary instance so options passed to a second
+        // instance are handled by the window that is already open
+        let app = self.obj();
+        app.activate();
+
+        let options = command_line.options_dict();
+        let handlers = self.command_line_options.borrow().clone();
+        for handler in handlers {
+            handler(&app, &options);
+        }
+
+        let files = command_line
+            .arguments()
+            .iter()
+            .skip(1)
+            .map(|arg| command_line.create_file_for_arg(arg))
+            .collect::<Vec<_>>();
+        let files_handler = self.files_handler.borrow().clone();
+        if let Some(files_handler) = files_handler {
+            if !files.is_empty() {
+                files_handler(&app, files);
+            }
+        }
+
+        glib::ExitCode::SUCCESS
+    }
+
+    fn shutdown(&self) {
+        // Save the windows that are still open in case they aren't closed
+        // before the application exits
+        for window in self.obj().windows() {
+            if let Some(state) = WindowState::for_window(&window) {
+                state.save(&window);
+            }
+        }
+
+        // Shut down users first so dependencies outlive the things that
+        // were built from them
+        let hooks = shutdown_order(&self.lifecycle.take(), &self.uses.borrow());
+        let app = self.obj().clone();
+        let timeout = self.shutdown_timeout.get().unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+        let context = glib::MainContext::default();
+        for hook in hooks {
+            let Some(future) = hook.hook.on_shutdown(&app) else {
+                continue;
+            };
+            if context
+                .block_on(glib::future_with_timeout(timeout, future))
+                .is_err()
+            {
+                let name = hook.type_.map_or("A lifecycle hook", |type_| type_.name());
+                glib::g_warning!(
+                    "rose",
+                    "{name} didn't shut down within {} seconds",
+                    timeout.as_secs_f32()
+                );
+            }
+        }
+
+        // Give the tasks that are still running a chance to finish
+        context.block_on(crate::runtime::shutdown(timeout));
+
+        self.parent_shutdown();
     }
 }
-impl ApplicationImpl for Application {}
 impl GtkApplicationImpl for Application {}
 impl AdwApplicationImpl for Application {}
 impl RoseApplicationImpl for Application {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::AssertUnwindSafe;
+
+    #[test]
+    fn factories_in_a_cycle_panic_with_the_chain() {
+        let app = glib::Object::new::<super::super::Application>();
+        app.add_singleton(|app| {
+            let _: gio::SimpleActionGroup = app.get_dependency();
+            gio::Menu::new()
+        });
+        app.add_singleton(|app| {
+            let _: gio::Menu = app.get_dependency();
+            gio::SimpleActionGroup::new()
+        });
+
+        let panic = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _: gio::Menu = app.get_dependency();
+        }))
+        .unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            message,
+            "Dependency cycle detected: GMenu -> GSimpleActionGroup -> GMenu"
+        );
+        assert!(app.imp().resolving.borrow().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use gtk::glib;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// A future returned by `Lifecycle::on_shutdown`
+pub type ShutdownFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Hooks for dependencies that need to do work when the application
+/// starts up or shuts down like flushing caches or closing files.
+///
+/// Hooks are called in the order the dependencies were added on startup.
+/// On shutdown the hooks of dependencies are called after the hooks of the
+/// dependencies whose factories used them, and the other hooks are called
+/// in the reverse order they were added.
+///
+/// # Example
+/// ```
+/// impl Lifecycle for Cache {
+///     fn on_start(&self, _app: &rose::Application) {
+///         self.load();
+///     }
+///
+///     fn on_shutdown(&self, _app: &rose::Application) -> Option<ShutdownFuture> {
+///         let cache = self.clone();
+///         Some(Box::pin(async move { cache.flush().await }))
+///     }
+/// }
+///
+/// app.add_managed_dependency(&Cache::new());
+/// ```
+pub trait Lifecycle: 'static {
+    /// Called when the application starts up. If the application has
+    /// already started this is called as soon as the hook is added.
+    fn on_start(&self, _app: &super::Application) {}
+
+    /// Called when the application shuts down. The application waits for
+    /// the returned future until the shutdown timeout runs out before it
+    /// calls the next hook.
+    fn on_shutdown(&self, _app: &super::Application) -> Option<ShutdownFuture> {
+        None
+    }
+}
+
+impl<T: Lifecycle> Lifecycle for Rc<T> {
+    fn on_start(&self, app: &super::Application) {
+        self.as_ref().on_start(app);
+    }
+
+    fn on_shutdown(&self, app: &super::Application) -> Option<ShutdownFuture> {
+        self.as_ref().on_shutdown(app)
+    }
+}
+
+/// A lifecycle hook that has been added to the application
+#[derive(Clone)]
+pub struct LifecycleHook {
+    pub hook: Rc<dyn Lifecycle>,
+    /// The type of the dependency the hook belongs to
+    pub type_: Option<glib::Type>,
+}
+
+impl std::fmt::Debug for LifecycleHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LifecycleHook")
+            .field("type_", &self.type_)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Orders the hooks so the hook of a dependency comes after the hooks of
+/// the dependencies that use it. `uses` holds the type of a dependency and
+/// the type of a dependency its factory used. Hooks that aren't related
+/// are in the reverse order they were added.
+pub fn shutdown_order(
+    hooks: &[LifecycleHook],
+    uses: &[(glib::Type, glib::Type)],
+) -> Vec<LifecycleHook> {
+    let mut visited = vec![false; hooks.len()];
+    let mut order = Vec::with_capacity(hooks.len());
+    for index in (0..hooks.len()).rev() {
+        visit_users(index, hooks, uses, &mut visited, &mut order);
+    }
+    order
+}
+
+fn visit_users(
+    index: usize,
+    hooks: &[LifecycleHook],
+    uses: &[(glib::Type, glib::Type)],
+    visited: &mut [bool],
+    order: &mut Vec<LifecycleHook>,
+) {
+    if visited[index] {
+        return;
+    }
+    visited[index] = true;
+    if let Some(used) = hooks[index].type_ {
+        for user in (0..hooks.len()).rev() {
+            let is_user = hooks[user]
+                .type_
+                .is_some_and(|user| uses.iter().any(|(a, b)| user.is_a(*a) && used.is_a(*b)));
+            if user != index && is_user {
+                visit_users(user, hooks, uses, visited, order);
+            }
+        }
+    }
+    order.push(hooks[index].clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Hook;
+
+    impl Lifecycle for Hook {}
+
+    fn hook(type_: Option<glib::Type>) -> LifecycleHook {
+        LifecycleHook {
+            hook: Rc::new(Hook),
+            type_,
+        }
+    }
+
+    fn types(hooks: &[LifecycleHook]) -> Vec<Option<glib::Type>> {
+        hooks.iter().map(|hook| hook.type_).collect()
+    }
+
+    #[test]
+    fn unrelated_hooks_shut_down_in_reverse() {
+        let hooks = [
+            hook(Some(glib::Type::STRING)),
+            hook(None),
+            hook(Some(glib::Type::I32)),
+        ];
+        assert_eq!(
+            types(&shutdown_order(&hooks, &[])),
+            [Some(glib::Type::I32), None, Some(glib::Type::STRING)]
+        );
+    }
+
+    #[test]
+    fn users_shut_down_before_what_they_use() {
+        // The string was added first but uses the int which uses the bool
+        let hooks = [
+            hook(Some(glib::Type::STRING)),
+            hook(Some(glib::Type::BOOL)),
+            hook(Some(glib::Type::I32)),
+        ];
+        let uses = [
+            (glib::Type::STRING, glib::Type::I32),
+            (glib::Type::I32, glib::Type::BOOL),
+        ];
+        assert_eq!(
+            types(&shutdown_order(&hooks, &uses)),
+            [
+                Some(glib::Type::STRING),
+                Some(glib::Type::I32),
+                Some(glib::Type::BOOL)
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_are_ordered_once() {
+        let hooks = [hook(Some(glib::Type::STRING)), hook(Some(glib::Type::I32))];
+        let uses = [
+            (glib::Type::STRING, glib::Type::I32),
+            (glib::Type::I32, glib::Type::STRING),
+        ];
+        assert_eq!(shutdown_order(&hooks, &uses).len(), 2);
+    }
+}
@@ -1,10 +1,26 @@
 use gtk::{gio, glib};
 
+pub mod actions;
+pub mod builder;
+pub mod command_line;
+pub mod dependency;
 pub mod ext;
 mod imp;
+pub mod lifecycle;
+pub mod notification;
 pub mod subclass;
+pub mod window_state;
 
 glib::wrapper! {
     pub struct Application(ObjectSubclass<imp::Application>)
-        @extends adw::Application, gtk::Application, gio::Application;
+        @extends adw::Application, gtk::Application, gio::Application,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+impl Application {
+    /// Creates a builder for an application with its routes, dependencies
+    /// and window. See `ApplicationBuilder`
+    pub fn builder() -> builder::ApplicationBuilder {
+        builder::ApplicationBuilder::new()
+    }
 }
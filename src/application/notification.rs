@@ -0,0 +1,124 @@
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use super::actions::{navigate_target, NAVIGATE_ACTION};
+use crate::PageRoute;
+
+/// A button on a `RouteNotification` that opens a route when clicked
+#[derive(Debug, Clone)]
+pub struct NotificationButton {
+    pub label: String,
+    /// The `(sv)` target of the `app.navigate` action
+    pub target: glib::Variant,
+}
+
+/// A desktop notification that opens a route when it or one of its
+/// buttons is clicked. Clicking activates `app.navigate` so the route is
+/// opened even if the application was closed when it was clicked.
+///
+/// # Example
+/// ```
+/// let notification = RouteNotification::new("Call Alice")
+///     .body("Alice's birthday is today")
+///     .priority(gio::NotificationPriority::High)
+///     .default_route::<ContactRoute>(Some("alice".to_string()))
+///     .button::<ReminderRoute>("Snooze", Some(reminder.id()));
+/// app.send_route_notification(Some("birthday-alice"), &notification);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RouteNotification {
+    pub title: String,
+    pub body: Option<String>,
+    pub priority: gio::NotificationPriority,
+    /// The `(sv)` target of the `app.navigate` action that is activated
+    /// when the notification is clicked
+    pub default_target: Option<glib::Variant>,
+    pub buttons: Vec<NotificationButton>,
+}
+
+impl RouteNotification {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            body: None,
+            priority: gio::NotificationPriority::Normal,
+            default_target: None,
+            buttons: Vec::new(),
+        }
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    pub fn priority(mut self, priority: gio::NotificationPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the route that is opened when the notification is clicked
+    pub fn default_route<R>(mut self, parameter: Option<R::Parameter>) -> Self
+    where
+        R: PageRoute + 'static,
+    {
+        self.default_target = Some(navigate_target::<R>(parameter));
+        self
+    }
+
+    /// Adds a button that opens a route when it is clicked
+    pub fn button<R>(mut self, label: &str, parameter: Option<R::Parameter>) -> Self
+    where
+        R: PageRoute + 'static,
+    {
+        self.buttons.push(NotificationButton {
+            label: label.to_string(),
+            target: navigate_target::<R>(parameter),
+        });
+        self
+    }
+
+    /// Builds the `gio::Notification` that is sent to the desktop
+    pub fn to_notification(&self) -> gio::Notification {
+        let action = format!("app.{NAVIGATE_ACTION}");
+        let notification = gio::Notification::new(&self.title);
+        notification.set_body(self.body.as_deref());
+        notification.set_priority(self.priority);
+        if let Some(target) = &self.default_target {
+            notification.set_default_action_and_target_value(&action, Some(target));
+        }
+        for button in &self.buttons {
+            notification.add_button_with_target_value(&button.label, &action, Some(&button.target));
+        }
+        notification
+    }
+}
+
+/// Sends notifications for the application. By default notifications are
+/// sent to the desktop with `gio::Application::send_notification`. A
+/// different backend can be added as a dependency, which is useful for
+/// testing without a notification service.
+///
+/// # Example
+/// ```
+/// app.add_shared_value_dependency::<dyn NotificationBackend>(Rc::new(MyBackend::new()));
+/// ```
+pub trait NotificationBackend: 'static {
+    fn send(&self, app: &super::Application, id: Option<&str>, notification: &RouteNotification);
+
+    fn withdraw(&self, app: &super::Application, id: &str);
+}
+
+/// Sends notifications to the desktop through GIO
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DesktopNotificationBackend;
+
+impl NotificationBackend for DesktopNotificationBackend {
+    fn send(&self, app: &super::Application, id: Option<&str>, notification: &RouteNotification) {
+        app.send_notification(id, &notification.to_notification());
+    }
+
+    fn withdraw(&self, app: &super::Application, id: &str) {
+        app.withdraw_notification(id);
+    }
+}
@@ -0,0 +1,261 @@
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::prelude::*;
+
+const WIDTH_KEY: &str = "width";
+const HEIGHT_KEY: &str = "height";
+const MAXIMIZED_KEY: &str = "is-maximized";
+const SIDEBAR_WIDTH_KEY: &str = "sidebar-width";
+const SIDEBAR_FRACTION_KEY: &str = "sidebar-width-fraction";
+
+/// The key `attach` keeps the state on the window with
+const STATE_KEY: &str = "rose-window-state";
+
+/// Where the window state is saved
+#[derive(Debug, Clone)]
+enum Store {
+    Settings(gio::Settings),
+    KeyFile { path: PathBuf, group: String },
+}
+
+/// The widget whose sidebar width is saved
+#[derive(Debug, Clone)]
+enum Sidebar {
+    Paned(glib::WeakRef<gtk::Paned>),
+    OverlaySplitView(glib::WeakRef<adw::OverlaySplitView>),
+    NavigationSplitView(glib::WeakRef<adw::NavigationSplitView>),
+}
+
+/// Saves and restores the size, maximized state and sidebar width of a
+/// window. The state is saved per window role so an app with more than
+/// one kind of window can remember each of them.
+///
+/// If the relocatable schema `<app id>.WindowState` is installed the state
+/// is saved with `gio::Settings` at `/<app/id>/window-state/<role>/`. The
+/// schema needs the integer keys `width`, `height` and `sidebar-width` and
+/// the boolean key `is-maximized`. Split views save their sidebar width as
+/// a fraction in the double key `sidebar-width-fraction` if the schema has
+/// it. Otherwise the state is saved to `window-state.ini` in the app's
+/// folder in the user data dir.
+///
+/// The state is saved when the window is closed or unrealized and when
+/// the application shuts down. Windows made by `ApplicationBuilder` keep
+/// their state so pages can find it with `WindowState::for_window`.
+///
+/// # Example
+/// ```
+/// let window = adw::ApplicationWindow::new(&app);
+/// let state = WindowState::attach(&window, "com.example.App", "main");
+/// state.track_overlay_split_view(&split_view);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WindowState {
+    store: Store,
+    sidebar: Rc<RefCell<Option<Sidebar>>>,
+}
+
+impl WindowState {
+    pub fn new(application_id: &str, role: &str) -> Self {
+        let schema_id = format!("{application_id}.WindowState");
+        let has_schema = gio::SettingsSchemaSource::default()
+            .and_then(|source| source.lookup(&schema_id, true))
+            .is_some();
+
+        let store = if has_schema {
+            let path = format!("/{}/window-state/{role}/", application_id.replace('.', "/"));
+            Store::Settings(gio::Settings::with_path(&schema_id, &path))
+        } else {
+            Store::KeyFile {
+                path: glib::user_data_dir()
+                    .join(application_id)
+                    .join("window-state.ini"),
+                group: role.to_string(),
+            }
+        };
+
+        Self {
+            store,
+            sidebar: Rc::default(),
+        }
+    }
+
+    /// Restores the state of the window and saves it when the window is
+    /// closed or unrealized. The state is kept on the window so it can be
+    /// found with `for_window` and saved when the application shuts down.
+    pub fn attach(window: &impl IsA<gtk::Window>, application_id: &str, role: &str) -> Self {
+        let state = Self::new(application_id, role);
+        state.restore(window);
+
+        let this = state.clone();
+        window.connect_close_request(move |window| {
+            this.save(window);
+            glib::Propagation::Proceed
+        });
+        let this = state.clone();
+        window
+            .upcast_ref::<gtk::Window>()
+            .connect_unrealize(move |window| {
+                if let Some(window) = window.downcast_ref::<gtk::Window>() {
+                    this.save(window);
+                }
+            });
+        unsafe {
+            window.set_data(STATE_KEY, state.clone());
+        }
+        state
+    }
+
+    /// Gets the state that was attached to the window
+    pub fn for_window(window: &impl IsA<gtk::Window>) -> Option<Self> {
+        unsafe {
+            window
+                .data::<WindowState>(STATE_KEY)
+                .map(|state| state.as_ref().clone())
+        }
+    }
+
+    /// Restores and saves the position of a paned as the sidebar width
+    pub fn track_paned(&self, paned: &gtk::Paned) {
+        if let Some(width) = self.integer(SIDEBAR_WIDTH_KEY) {
+            paned.set_position(width);
+        }
+        self.sidebar.replace(Some(Sidebar::Paned(paned.week_ref())));
+    }
+
+    /// Restores and saves the sidebar width fraction of a split view
+    pub fn track_overlay_split_view(&self, split_view: &adw::OverlaySplitView) {
+        if let Some(fraction) = self.double(SIDEBAR_FRACTION_KEY) {
+            split_view.set_sidebar_width_fraction(fraction);
+        }
+        self.sidebar
+            .replace(Some(Sidebar::OverlaySplitView(split_view.week_ref())));
+    }
+
+    /// Restores and saves the sidebar width fraction of a split view
+    pub fn track_navigation_split_view(&self, split_view: &adw::NavigationSplitView) {
+        if let Some(fraction) = self.double(SIDEBAR_FRACTION_KEY) {
+            split_view.set_sidebar_width_fraction(fraction);
+        }
+        self.sidebar
+            .replace(Some(Sidebar::NavigationSplitView(split_view.week_ref())));
+    }
+
+    pub fn restore(&self, window: &impl IsA<gtk::Window>) {
+        if let (Some(width), Some(height)) = (self.integer(WIDTH_KEY), self.integer(HEIGHT_KEY)) {
+            window.set_default_size(width, height);
+        }
+        if self.boolean(MAXIMIZED_KEY).unwrap_or(false) {
+            window.maximize();
+        }
+    }
+
+    pub fn save(&self, window: &impl IsA<gtk::Window>) {
+        // The default size is the size of the window when it isn't
+        // maximized which is what should be restored
+        let (width, height) = window.default_size();
+        let sidebar = self.sidebar.borrow().clone();
+        let sidebar_width = match &sidebar {
+            Some(Sidebar::Paned(paned)) => paned.upgrade().map(|paned| paned.position()),
+            _ => None,
+        };
+        let sidebar_fraction = match &sidebar {
+            Some(Sidebar::OverlaySplitView(split_view)) => split_view
+                .upgrade()
+                .map(|split_view| split_view.sidebar_width_fraction()),
+            Some(Sidebar::NavigationSplitView(split_view)) => split_view
+                .upgrade()
+                .map(|split_view| split_view.sidebar_width_fraction()),
+            _ => None,
+        };
+
+        let result = match &self.store {
+            Store::Settings(settings) => {
+                let mut result = settings
+                    .set_int(WIDTH_KEY, width)
+                    .and_then(|_| settings.set_int(HEIGHT_KEY, height))
+                    .and_then(|_| settings.set_boolean(MAXIMIZED_KEY, window.is_maximized()));
+                if let Some(sidebar_width) = sidebar_width {
+                    result =
+                        result.and_then(|_| settings.set_int(SIDEBAR_WIDTH_KEY, sidebar_width));
+                }
+                if let Some(fraction) =
+                    sidebar_fraction.filter(|_| has_key(settings, SIDEBAR_FRACTION_KEY))
+                {
+                    result =
+                        result.and_then(|_| settings.set_double(SIDEBAR_FRACTION_KEY, fraction));
+                }
+                result.map_err(|error| error.to_string())
+            }
+            Store::KeyFile { path, group } => {
+                let key_file = load_key_file(path);
+                key_file.set_integer(group, WIDTH_KEY, width);
+                key_file.set_integer(group, HEIGHT_KEY, height);
+                key_file.set_boolean(group, MAXIMIZED_KEY, window.is_maximized());
+                if let Some(sidebar_width) = sidebar_width {
+                    key_file.set_integer(group, SIDEBAR_WIDTH_KEY, sidebar_width);
+                }
+                if let Some(fraction) = sidebar_fraction {
+                    key_file.set_double(group, SIDEBAR_FRACTION_KEY, fraction);
+                }
+                path.parent()
+                    .map(std::fs::create_dir_all)
+                    .transpose()
+                    .map_err(|error| error.to_string())
+                    .and_then(|_| {
+                        key_file
+                            .save_to_file(path)
+                            .map_err(|error| error.to_string())
+                    })
+            }
+        };
+
+        if let Err(error) = result {
+            glib::g_warning!("rose", "Failed to save window state: {error}");
+        }
+    }
+
+    /// Gets a size from the store. Sizes that are 0 haven't been saved yet
+    fn integer(&self, key: &str) -> Option<i32> {
+        let value = match &self.store {
+            Store::Settings(settings) => Some(settings.int(key)),
+            Store::KeyFile { path, group } => load_key_file(path).integer(group, key).ok(),
+        };
+        value.filter(|value| *value > 0)
+    }
+
+    /// Gets a fraction from the store. Fractions that are 0 haven't been
+    /// saved yet
+    fn double(&self, key: &str) -> Option<f64> {
+        let value = match &self.store {
+            Store::Settings(settings) if has_key(settings, key) => Some(settings.double(key)),
+            Store::Settings(_) => None,
+            Store::KeyFile { path, group } => load_key_file(path).double(group, key).ok(),
+        };
+        value.filter(|value| *value > 0.0)
+    }
+
+    fn boolean(&self, key: &str) -> Option<bool> {
+        match &self.store {
+            Store::Settings(settings) => Some(settings.boolean(key)),
+            Store::KeyFile { path, group } => load_key_file(path).boolean(group, key).ok(),
+        }
+    }
+}
+
+/// Checks if the schema has one of the optional keys
+fn has_key(settings: &gio::Settings, key: &str) -> bool {
+    settings
+        .settings_schema()
+        .is_some_and(|schema| schema.has_key(key))
+}
+
+/// Loads the key file or creates an empty one if it doesn't exist yet
+fn load_key_file(path: &Path) -> glib::KeyFile {
+    let key_file = glib::KeyFile::new();
+    let _ = key_file.load_from_file(path, glib::KeyFileFlags::KEEP_COMMENTS);
+    key_file
+}
@@ -0,0 +1,352 @@
+use gtk::glib::{self, prelude::*};
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::panic::Location;
+use std::rc::{Rc, Weak};
+
+use crate::failure;
+use crate::utils::widget::{connect_widget_gone, WidgetGone};
+use crate::{Channel, Sender, Subscription};
+
+type Event = Box<dyn Any + Send>;
+type Handler = Rc<dyn Fn(&dyn Any)>;
+
+struct Subscriber {
+    id: u64,
+    handler: Handler,
+    /// Keeps the handlers that unsubscribe a widget's callback connected
+    /// for as long as it's subscribed
+    widget_gone: Option<WidgetGone>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    by_type: RefCell<HashMap<TypeId, Vec<Subscriber>>>,
+    next_id: Cell<u64>,
+}
+
+impl Subscribers {
+    fn dispatch(&self, event: Event) {
+        let event = event.as_ref();
+        // Clone the handlers so they can subscribe and unsubscribe
+        let handlers: Vec<_> = self
+            .by_type
+            .borrow()
+            .get(&event.type_id())
+            .map(|subscribers| {
+                subscribers
+                    .iter()
+                    .map(|subscriber| subscriber.handler.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for handler in handlers {
+            handler(event);
+        }
+    }
+
+    fn remove(&self, type_id: TypeId, id: u64) {
+        // Drop the subscriber after the borrow ends since dropping its
+        // guard disconnects signal handlers
+        let removed = self
+            .by_type
+            .borrow_mut()
+            .get_mut(&type_id)
+            .and_then(|subscribers| {
+                let index = subscribers
+                    .iter()
+                    .position(|subscriber| subscriber.id == id)?;
+                Some(subscribers.remove(index))
+            });
+        drop(removed);
+    }
+
+    fn set_widget_gone(&self, type_id: TypeId, id: u64, widget_gone: WidgetGone) {
+        let mut by_type = self.by_type.borrow_mut();
+        let subscriber = by_type.get_mut(&type_id).and_then(|subscribers| {
+            subscribers
+                .iter_mut()
+                .find(|subscriber| subscriber.id == id)
+        });
+        if let Some(subscriber) = subscriber {
+            subscriber.widget_gone = Some(widget_gone);
+        }
+    }
+}
+
+/// Sends typed events between pages that don't know about each other.
+/// Events are delivered on the main loop in the order they were published
+/// to every subscriber of their type. The application's bus is added as a
+/// value dependency by `RoseApplicationExt::event_bus`.
+///
+/// # Example
+/// ```
+/// struct ContactDeleted(String);
+///
+/// let bus = app.event_bus();
+/// bus.subscribe_for(&list, |list, event: &ContactDeleted| {
+///     list.remove_contact(&event.0);
+/// })
+/// .forget();
+///
+/// let publisher = bus.publisher();
+/// rose::spawn_async(delete_contact(id.clone()), move |_| {
+///     publisher.publish(ContactDeleted(id));
+/// });
+/// ```
+pub struct EventBus {
+    sender: Sender<Event>,
+    subscribers: Rc<Subscribers>,
+    _delivery: Subscription,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus").finish_non_exhaustive()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, receiver) = Channel::new::<Event>();
+        let subscribers = Rc::new(Subscribers::default());
+        let delivery = {
+            let subscribers = subscribers.clone();
+            receiver.attach(move |event| subscribers.dispatch(event))
+        };
+        Self {
+            sender,
+            subscribers,
+            _delivery: delivery,
+        }
+    }
+
+    /// Publishes an event. It's delivered to the subscribers the next time
+    /// the main loop runs.
+    pub fn publish<E: Any + Send>(&self, event: E) {
+        self.publisher().publish(event);
+    }
+
+    /// Gets a publisher that can be sent to other threads
+    pub fn publisher(&self) -> EventPublisher {
+        EventPublisher {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Calls the callback on the main thread for every event of type `E`
+    /// until the returned subscription is dropped
+    #[track_caller]
+    pub fn subscribe<E, F>(&self, callback: F) -> EventSubscription
+    where
+        E: Any + Send,
+        F: Fn(&E) + 'static,
+    {
+        let location = Location::caller();
+        let id = self.add::<E>(Rc::new(move |event| {
+            if let Some(event) = event.downcast_ref::<E>() {
+                failure::catch_callback(location, || callback(event));
+            }
+        }));
+        EventSubscription {
+            subscribers: Rc::downgrade(&self.subscribers),
+            type_id: TypeId::of::<E>(),
+            id: Some(id),
+        }
+    }
+
+    /// Calls the callback with the widget for every event of type `E`. Only
+    /// a weak reference to the widget is kept and the subscription ends
+    /// when the widget is destroyed or the page it's in is popped or
+    /// replaced. It also ends when the returned subscription is dropped,
+    /// use `EventSubscription::forget` to keep it for as long as the
+    /// widget's route.
+    #[track_caller]
+    pub fn subscribe_for<E, W, F>(&self, widget: &W, callback: F) -> EventSubscription
+    where
+        E: Any + Send,
+        W: IsA<gtk::Widget>,
+        F: Fn(&W, &E) + 'static,
+    {
+        let location = Location::caller();
+        let widget_ref = widget.downgrade();
+        let id = self.add::<E>(Rc::new(move |event| {
+            let widget = widget_ref.upgrade();
+            if let (Some(widget), Some(event)) = (widget, event.downcast_ref::<E>()) {
+                failure::catch_callback(location, || callback(&widget, event));
+            }
+        }));
+
+        let subscribers = Rc::downgrade(&self.subscribers);
+        let widget_gone = connect_widget_gone(widget.upcast_ref(), move || {
+            if let Some(subscribers) = subscribers.upgrade() {
+                subscribers.remove(TypeId::of::<E>(), id);
+            }
+        });
+        self.subscribers
+            .set_widget_gone(TypeId::of::<E>(), id, widget_gone);
+
+        EventSubscription {
+            subscribers: Rc::downgrade(&self.subscribers),
+            type_id: TypeId::of::<E>(),
+            id: Some(id),
+        }
+    }
+
+    fn add<E: Any>(&self, handler: Handler) -> u64 {
+        let id = self.subscribers.next_id.get();
+        self.subscribers.next_id.set(id + 1);
+        self.subscribers
+            .by_type
+            .borrow_mut()
+            .entry(TypeId::of::<E>())
+            .or_default()
+            .push(Subscriber {
+                id,
+                handler,
+                widget_gone: None,
+            });
+        id
+    }
+}
+
+/// Publishes events to an `EventBus` from any thread
+#[derive(Clone)]
+pub struct EventPublisher {
+    sender: Sender<Event>,
+}
+
+impl EventPublisher {
+    /// Publishes an event. Events published after the bus was dropped are
+    /// ignored.
+    pub fn publish<E: Any + Send>(&self, event: E) {
+        if self.sender.send(Box::new(event)).is_err() {
+            glib::g_warning!(
+                "rose",
+                "Can't publish an event after the event bus was dropped"
+            );
+        }
+    }
+}
+
+/// Keeps a callback subscribed to an `EventBus`. The callback is
+/// unsubscribed when the subscription is dropped.
+#[must_use = "The callback is unsubscribed when the subscription is dropped"]
+pub struct EventSubscription {
+    subscribers: Weak<Subscribers>,
+    type_id: TypeId,
+    id: Option<u64>,
+}
+
+impl EventSubscription {
+    /// Keeps the callback subscribed for as long as the bus exists
+    pub fn forget(mut self) {
+        self.id.take();
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        if let (Some(subscribers), Some(id)) = (self.subscribers.upgrade(), self.id) {
+            subscribers.remove(self.type_id, id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ContactDeleted(&'static str);
+    struct ContactAdded(&'static str);
+
+    /// Runs the test with a new main context as the thread's default so
+    /// the bus delivers events when it's iterated
+    fn with_context(test: impl FnOnce(&glib::MainContext)) {
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| test(&context))
+            .expect("Failed to acquire the main context");
+    }
+
+    fn deliver(context: &glib::MainContext) {
+        while context.iteration(false) {}
+    }
+
+    fn record<E: Any + Send>(
+        bus: &EventBus,
+        name: fn(&E) -> &'static str,
+    ) -> (Rc<RefCell<Vec<&'static str>>>, EventSubscription) {
+        let names = Rc::new(RefCell::new(Vec::new()));
+        let recorded = names.clone();
+        let subscription = bus.subscribe(move |event: &E| recorded.borrow_mut().push(name(event)));
+        (names, subscription)
+    }
+
+    #[test]
+    fn events_are_delivered_in_order() {
+        with_context(|context| {
+            let bus = EventBus::new();
+            let (deleted, _subscription) = record(&bus, |event: &ContactDeleted| event.0);
+
+            bus.publish(ContactDeleted("alice"));
+            bus.publish(ContactDeleted("bob"));
+            assert!(deleted.borrow().is_empty());
+
+            deliver(context);
+            assert_eq!(*deleted.borrow(), ["alice", "bob"]);
+        });
+    }
+
+    #[test]
+    fn events_are_delivered_by_type() {
+        with_context(|context| {
+            let bus = EventBus::new();
+            let (deleted, _deleted_subscription) = record(&bus, |event: &ContactDeleted| event.0);
+            let (added, _added_subscription) = record(&bus, |event: &ContactAdded| event.0);
+
+            bus.publish(ContactAdded("alice"));
+            bus.publish(ContactDeleted("bob"));
+            deliver(context);
+            assert_eq!(*added.borrow(), ["alice"]);
+            assert_eq!(*deleted.borrow(), ["bob"]);
+        });
+    }
+
+    #[test]
+    fn events_are_published_from_other_threads() {
+        with_context(|context| {
+            let bus = EventBus::new();
+            let (deleted, _subscription) = record(&bus, |event: &ContactDeleted| event.0);
+
+            let publisher = bus.publisher();
+            std::thread::spawn(move || publisher.publish(ContactDeleted("alice")))
+                .join()
+                .unwrap();
+            deliver(context);
+            assert_eq!(*deleted.borrow(), ["alice"]);
+        });
+    }
+
+    #[test]
+    fn dropped_subscription_gets_no_events() {
+        with_context(|context| {
+            let bus = EventBus::new();
+            let (deleted, subscription) = record(&bus, |event: &ContactDeleted| event.0);
+
+            bus.publish(ContactDeleted("alice"));
+            deliver(context);
+            drop(subscription);
+            bus.publish(ContactDeleted("bob"));
+            deliver(context);
+            assert_eq!(*deleted.borrow(), ["alice"]);
+        });
+    }
+}
@@ -0,0 +1,174 @@
+use adw::prelude::*;
+use gtk::glib;
+use std::any::Any;
+use std::fmt;
+use std::panic::{AssertUnwindSafe, Location};
+
+use crate::application::actions::current_router;
+use crate::prelude::*;
+use crate::{Application, DisplayableError};
+
+/// Where a failure happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureSource {
+    /// A future spawned with `spawn_async` or a background thread
+    Task,
+    /// A callback that was called on the main thread
+    Callback,
+}
+
+impl fmt::Display for FailureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureSource::Task => write!(f, "background task"),
+            FailureSource::Callback => write!(f, "callback"),
+        }
+    }
+}
+
+/// A panic that was caught in a spawned task or a callback
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub message: String,
+    pub source: FailureSource,
+    /// Where the task or callback was created
+    pub location: Option<String>,
+}
+
+impl Failure {
+    pub fn new(message: &str, source: FailureSource, location: Option<&Location<'_>>) -> Self {
+        Self {
+            message: message.to_string(),
+            source,
+            location: location.map(|location| location.to_string()),
+        }
+    }
+
+    /// Creates a failure from the payload of a panic
+    pub fn from_panic(
+        payload: Box<dyn Any + Send>,
+        source: FailureSource,
+        location: Option<&Location<'_>>,
+    ) -> Self {
+        Self::new(&panic_message(payload.as_ref()), source, location)
+    }
+
+    /// Details about the failure that can be copied into a bug report
+    pub fn details(&self) -> String {
+        let mut details = format!("{}\n\nIn: {}", self.message, self.source);
+        if let Some(location) = &self.location {
+            details.push_str(&format!("\nSpawned at: {location}"));
+        }
+        details
+    }
+}
+
+impl DisplayableError for Failure {
+    fn title(&self) -> String {
+        crate::gettext("Something went wrong")
+    }
+
+    fn body(&self) -> String {
+        self.message.clone()
+    }
+}
+
+/// Gets the message a panic was started with
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Handles failures that were caught by rose. Apps can add their own
+/// reporter with `RoseApplicationExt::set_failure_reporter`, for example
+/// to send crash reports. By default `ErrorPageReporter` is used.
+pub trait FailureReporter: 'static {
+    fn report(&self, failure: &Failure);
+}
+
+/// Shows failures on the router's error page
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ErrorPageReporter;
+
+impl FailureReporter for ErrorPageReporter {
+    fn report(&self, failure: &Failure) {
+        if let Some(router) = Application::try_find().and_then(|app| current_router(&app)) {
+            router.show_error(failure);
+        }
+    }
+}
+
+/// Shows failures in a dialog with details that can be copied
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CrashDialogReporter;
+
+impl FailureReporter for CrashDialogReporter {
+    fn report(&self, failure: &Failure) {
+        let parent = Application::try_find().and_then(|app| app.active_window());
+        let dialog = adw::MessageDialog::new(
+            parent.as_ref(),
+            Some(&failure.title()),
+            Some(&failure.body()),
+        );
+
+        let details = gtk::Label::builder()
+            .label(failure.details())
+            .selectable(true)
+            .wrap(true)
+            .xalign(0.0)
+            .css_classes(["monospace"])
+            .build();
+        dialog.set_extra_child(Some(&details));
+        dialog.add_response("copy", &crate::gettext("Copy Details"));
+        dialog.add_response("close", &crate::gettext("Close"));
+        dialog.set_close_response("close");
+
+        let text = failure.details();
+        dialog.connect_response(Some("copy"), move |dialog, _| {
+            dialog.clipboard().set_text(&text);
+        });
+        dialog.present();
+    }
+}
+
+/// Logs a failure and passes it to the application's failure reporter.
+/// This must be called on the main thread.
+pub fn report_failure(failure: Failure) {
+    glib::g_critical!("rose", "{}", failure.details());
+
+    let reporter = Application::try_find()
+        .and_then(|app| app.try_get_value_dependency::<dyn FailureReporter>());
+    match reporter {
+        Some(reporter) => reporter.report(&failure),
+        None => ErrorPageReporter.report(&failure),
+    }
+}
+
+/// Calls a callback and reports it if it panics instead of letting the
+/// panic unwind into the main loop
+pub(crate) fn catch_callback<R>(
+    location: &Location<'_>,
+    callback: impl FnOnce() -> R,
+) -> Option<R> {
+    match std::panic::catch_unwind(AssertUnwindSafe(callback)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            report_failure(Failure::from_panic(
+                payload,
+                FailureSource::Callback,
+                Some(location),
+            ));
+            None
+        }
+    }
+}
+
+/// Reports a failure from another thread by sending it to the main thread
+pub(crate) fn report_failure_from_thread(failure: Failure) {
+    glib::MainContext::default().invoke(move || report_failure(failure));
+}
@@ -1,13 +1,21 @@
+use std::rc::Rc;
+
 use gtk::glib::{IsA, self, StaticTypeExt};
 
 use crate::{PageRoute, Application, prelude::RoseApplicationExt, Router, router::ext::RoseRouterExt, DisplayableError};
 
+/// Gets the router of the active window
+fn current_router() -> Router {
+    crate::application::actions::current_router(&Application::find())
+        .expect("Failed to get router")
+}
+
 pub fn visit<T: PageRoute>(parameter: Option<T::Parameter>) {
-    Application::find().get_dependency::<Router>().visit::<T>(parameter);
+    current_router().visit::<T>(parameter);
 }
 
 pub fn back() {
-    Application::find().get_dependency::<Router>().back();
+    current_router().back();
 }
 
 pub fn get_dependency<T: IsA<glib::Object>>() -> T {
@@ -18,8 +26,71 @@ pub fn add_dependency<T: IsA<glib::Object>>(dependency: &T) {
     Application::find().add_dependency::<T>(dependency);
 }
 
+pub fn get_dependency_named<T: IsA<glib::Object>>(name: &str) -> T {
+    Application::find().get_dependency_named::<T>(name)
+}
+
+pub fn add_dependency_named<T: IsA<glib::Object>>(name: &str, dependency: &T) {
+    Application::find().add_dependency_named::<T>(name, dependency);
+}
+
+pub fn add_value_dependency<T: 'static>(value: T) {
+    Application::find().add_value_dependency(value);
+}
+
+pub fn get_value_dependency<T: ?Sized + 'static>() -> Rc<T> {
+    Application::find().get_value_dependency::<T>()
+}
+
+pub fn try_get_value_dependency<T: ?Sized + 'static>() -> Option<Rc<T>> {
+    Application::try_find().and_then(|app| app.try_get_value_dependency::<T>())
+}
+
+pub fn has_value_dependency<T: ?Sized + 'static>() -> bool {
+    Application::try_find().is_some_and(|app| app.has_value_dependency::<T>())
+}
+
+pub fn replace_dependency<T: IsA<glib::Object>>(dependency: &T) -> Option<T> {
+    Application::find().replace_dependency::<T>(dependency)
+}
+
+pub fn replace_dependency_as<I, T>(dependency: &T) -> Option<I>
+where
+    I: IsA<glib::Object>,
+    T: IsA<I> + IsA<glib::Object>,
+{
+    Application::find().replace_dependency_as::<I, T>(dependency)
+}
+
+pub fn remove_dependency<T: IsA<glib::Object>>() -> Option<T> {
+    Application::find().remove_dependency::<T>()
+}
+
+pub fn add_singleton<T, F>(factory: F)
+where
+    T: IsA<glib::Object>,
+    F: Fn(&Application) -> T + 'static,
+{
+    Application::find().add_singleton(factory);
+}
+
+pub fn add_transient<T, F>(factory: F)
+where
+    T: IsA<glib::Object>,
+    F: Fn(&Application) -> T + 'static,
+{
+    Application::find().add_transient(factory);
+}
+
 pub fn show_error_page<T: DisplayableError>(error: T) {
-    Application::find().get_dependency::<Router>().show_error(&error);
+    current_router().show_error(&error);
+}
+
+/// Translates a string using the current gettext domain. This is a thin
+/// wrapper around `glib::dgettext` so apps don't need to depend on a
+/// gettext crate just to translate route titles.
+pub fn gettext(msgid: &str) -> String {
+    glib::dgettext(None, msgid).to_string()
 }
 
 pub fn init() {
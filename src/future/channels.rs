@@ -1,30 +1,117 @@
-use gtk::glib;
+use gtk::glib::{self, prelude::*};
+use std::panic::Location;
+
+use crate::failure;
+
+/// What a bounded channel does when a value is sent while it's full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait until there is room. `Sender::send` blocks the thread so it
+    /// shouldn't be used on the main thread, use `Sender::send_async` there.
+    #[default]
+    Block,
+    /// Drop the oldest value in the channel to make room
+    DropOldest,
+    /// Drop the value that is being sent
+    DropNewest,
+    /// Return the value that is being sent as `SendError::Full`
+    Error,
+}
+
+/// Why a value couldn't be sent. The value is given back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// Every receiver was dropped
+    Closed(T),
+    /// The channel is full and its policy is `OverflowPolicy::Error`
+    Full(T),
+}
+
+impl<T> SendError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Closed(value) | SendError::Full(value) => value,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        matches!(self, SendError::Full(_))
+    }
+}
+
+impl<T> std::fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Closed(_) => write!(f, "The channel is closed"),
+            SendError::Full(_) => write!(f, "The channel is full"),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for SendError<T> {}
 
 /// A channel for sending and receiving values.
 pub struct Channel;
 impl Channel {
     /// Create a new channel. The sender can be cloned and sent to other threads. The receiver
     /// can be attached to a callback. that callback will be called on the main thread when a
-    /// value is sent.
-    /// 
+    /// value is sent. The channel is closed when every sender or every receiver is dropped.
+    ///
     /// # Example
     /// ```
     /// let label = gtk::Label::new(Some("Hello"));
     /// let (sender, receiver) = rose::Channel::new();
-    /// 
-    /// RUNTIME.spawn(async move {
-    ///    sender.send("Hello from async land".to_string());
+    ///
+    /// rose::runtime::handle().spawn(async move {
+    ///    sender.send("Hello from async land".to_string()).ok();
     /// });
-    /// 
+    ///
     /// receiver.attach(move |resp| {
     ///   label.set_text(&resp);
-    /// });
+    /// })
+    /// .forget();
+    /// ```
+    #[allow(clippy::new_ret_no_self)]
     pub fn new<T>() -> (Sender<T>, Receiver<T>)
     where
         T: Send + 'static,
     {
         let (sender, receiver) = async_channel::unbounded::<T>();
-        let sender = Sender { inner: sender };
+        let sender = Sender {
+            inner: sender,
+            policy: OverflowPolicy::Block,
+            oldest: None,
+        };
+        let receiver = Receiver { inner: receiver };
+        (sender, receiver)
+    }
+
+    /// Create a channel that holds at most `capacity` values. The policy
+    /// decides what happens when a value is sent while the channel is
+    /// full. This panics if `capacity` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// // Only the latest position matters when the main thread is busy
+    /// let (sender, receiver) = rose::Channel::bounded(1, rose::OverflowPolicy::DropOldest);
+    /// ```
+    pub fn bounded<T>(capacity: usize, policy: OverflowPolicy) -> (Sender<T>, Receiver<T>)
+    where
+        T: Send + 'static,
+    {
+        assert!(
+            capacity > 0,
+            "A bounded channel needs a capacity of at least 1"
+        );
+        let (sender, receiver) = async_channel::bounded::<T>(capacity);
+        // A weak receiver so the sender can make room without keeping the
+        // channel open after the last receiver was dropped
+        let oldest = (policy == OverflowPolicy::DropOldest).then(|| receiver.downgrade());
+        let sender = Sender {
+            inner: sender,
+            policy,
+            oldest,
+        };
         let receiver = Receiver { inner: receiver };
         (sender, receiver)
     }
@@ -32,11 +119,67 @@ impl Channel {
 
 pub struct Sender<T> {
     inner: async_channel::Sender<T>,
+    policy: OverflowPolicy,
+    oldest: Option<async_channel::WeakReceiver<T>>,
 }
 
 impl<T> Sender<T> {
-    pub fn send(&self, value: T) {
-        self.inner.try_send(value).unwrap();
+    /// Sends a value. This fails if every receiver was dropped. If the
+    /// channel is full it's handled with the channel's `OverflowPolicy`.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.policy {
+            OverflowPolicy::Block => self
+                .inner
+                .send_blocking(value)
+                .map_err(|error| SendError::Closed(error.into_inner())),
+            _ => self.send_without_waiting(value),
+        }
+    }
+
+    /// Sends a value and waits for room if the channel is full and its
+    /// policy is `OverflowPolicy::Block`
+    pub async fn send_async(&self, value: T) -> Result<(), SendError<T>> {
+        match self.policy {
+            OverflowPolicy::Block => self
+                .inner
+                .send(value)
+                .await
+                .map_err(|error| SendError::Closed(error.into_inner())),
+            _ => self.send_without_waiting(value),
+        }
+    }
+
+    fn send_without_waiting(&self, mut value: T) -> Result<(), SendError<T>> {
+        loop {
+            match self.inner.try_send(value) {
+                Ok(()) => return Ok(()),
+                Err(async_channel::TrySendError::Closed(value)) => {
+                    return Err(SendError::Closed(value))
+                }
+                Err(async_channel::TrySendError::Full(rejected))
+                    if self.policy == OverflowPolicy::Error =>
+                {
+                    return Err(SendError::Full(rejected))
+                }
+                Err(async_channel::TrySendError::Full(rejected)) => {
+                    let receiver = self.oldest.as_ref().and_then(|oldest| oldest.upgrade());
+                    match receiver {
+                        // Another sender might fill the room again so try
+                        // until the value fits
+                        Some(receiver) => {
+                            let _ = receiver.try_recv();
+                            value = rejected;
+                        }
+                        None if self.inner.is_closed() => return Err(SendError::Closed(rejected)),
+                        None => return Ok(()),
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
     }
 }
 
@@ -44,16 +187,12 @@ impl<T> Clone for Sender<T> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            policy: self.policy,
+            oldest: self.oldest.clone(),
         }
     }
 }
 
-impl<T> Drop for Sender<T> {
-    fn drop(&mut self) {
-        self.inner.close();
-    }
-}
-
 pub struct Receiver<T>
 where
     T: Send + 'static,
@@ -67,17 +206,77 @@ where
 {
     /// Attach a callback to the receiver. The callback will be called on the main thread when a
     /// value is sent. GObjects can be safely cloned and used by this callback since it will be
-    /// called on the main thread.
-    pub fn attach<F>(&self, callback: F)
+    /// called on the main thread. If the callback panics the panic is passed to the
+    /// application's failure reporter and the callback is detached.
+    ///
+    /// The callback is detached when the returned `Subscription` is dropped. Use
+    /// `Subscription::forget` to keep it attached until the channel is closed. This is a
+    /// breaking change, before the callback stayed attached after the `attach` call and code
+    /// that ignores the subscription now detaches the callback right away.
+    ///
+    /// # Example
+    /// ```
+    /// let subscription = receiver.attach(move |progress| {
+    ///     label.set_text(&progress);
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn attach<F>(&self, callback: F) -> Subscription
     where
-        F: Fn(T) + Send + 'static,
+        F: Fn(T) + 'static,
+    {
+        Subscription::new(self.attach_local(Location::caller(), callback))
+    }
+
+    /// Attach a callback that is given the object instead of capturing it. Only a weak
+    /// reference to the object is kept and the callback is detached when the object is
+    /// disposed or the returned `Subscription` is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// receiver
+    ///     .attach_weak(&label, |label, progress: String| {
+    ///         label.set_text(&progress);
+    ///     })
+    ///     .forget();
+    /// ```
+    #[track_caller]
+    pub fn attach_weak<O, F>(&self, object: &O, callback: F) -> Subscription
+    where
+        O: IsA<glib::Object>,
+        F: Fn(&O, T) + 'static,
+    {
+        let object_ref = object.downgrade();
+        let handle = self.attach_local(Location::caller(), move |value| {
+            if let Some(object) = object_ref.upgrade() {
+                callback(&object, value);
+            }
+        });
+        let source = handle.source().clone();
+        let notify = object.add_weak_ref_notify_local(move || source.destroy());
+        // The notify holds the source, so it's disconnected with the
+        // subscription to let the callback and its receiver be freed
+        Subscription::new(handle).on_detach(move || notify.disconnect())
+    }
+
+    /// Attaches a callback that doesn't have to be `Send`. The returned
+    /// handle can be aborted to detach the callback.
+    pub(crate) fn attach_local<F>(
+        &self,
+        location: &'static Location<'static>,
+        callback: F,
+    ) -> glib::JoinHandle<()>
+    where
+        F: Fn(T) + 'static,
     {
         let inner = self.inner.clone();
         glib::spawn_future_local(async move {
             while let Ok(value) = inner.recv().await {
-                callback(value);
+                if failure::catch_callback(location, || callback(value)).is_none() {
+                    break;
+                }
             }
-        });
+        })
     }
 }
 
@@ -92,11 +291,140 @@ where
     }
 }
 
-impl<T> Drop for Receiver<T>
-where
-    T: Send + 'static,
-{
+/// Keeps a callback attached to a `Receiver`. The callback is detached
+/// when the subscription is dropped.
+#[must_use = "The callback is detached when the subscription is dropped"]
+pub struct Subscription {
+    handle: Option<glib::JoinHandle<()>>,
+    /// Called when the callback is detached to undo what keeps it attached
+    on_detach: Vec<Box<dyn FnOnce()>>,
+}
+
+impl std::fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Subscription {
+    pub(crate) fn new(handle: glib::JoinHandle<()>) -> Self {
+        Self {
+            handle: Some(handle),
+            on_detach: Vec::new(),
+        }
+    }
+
+    /// Calls `f` when the callback is detached. `f` and what it captures
+    /// are leaked if the subscription is forgotten.
+    pub(crate) fn on_detach(mut self, f: impl FnOnce() + 'static) -> Self {
+        self.on_detach.push(Box::new(f));
+        self
+    }
+
+    /// The source of the attached callback, destroying it detaches the
+    /// callback
+    pub(crate) fn source(&self) -> Option<&glib::Source> {
+        self.handle.as_ref().map(|handle| handle.source())
+    }
+
+    /// Detaches the callback now
+    pub fn detach(mut self) {
+        self.end();
+    }
+
+    /// Keeps the callback attached until the channel is closed
+    pub fn forget(mut self) {
+        self.handle.take();
+        std::mem::forget(std::mem::take(&mut self.on_detach));
+    }
+
+    fn end(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+        for f in self.on_detach.drain(..) {
+            f();
+        }
+    }
+}
+
+impl Drop for Subscription {
     fn drop(&mut self) {
-        self.inner.close();
+        self.end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received<T: Send>(receiver: &Receiver<T>) -> Vec<T> {
+        std::iter::from_fn(|| receiver.inner.try_recv().ok()).collect()
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity of at least 1")]
+    fn bounded_rejects_zero_capacity() {
+        let _ = Channel::bounded::<i32>(0, OverflowPolicy::Block);
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let (sender, receiver) = Channel::bounded(1, OverflowPolicy::Block);
+        sender.send(1).unwrap();
+
+        let thread = std::thread::spawn(move || sender.send(2));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!thread.is_finished());
+        assert_eq!(receiver.inner.recv_blocking(), Ok(1));
+
+        thread.join().unwrap().unwrap();
+        assert_eq!(received(&receiver), [2]);
+    }
+
+    #[test]
+    fn drop_newest_drops_the_sent_value() {
+        let (sender, receiver) = Channel::bounded(2, OverflowPolicy::DropNewest);
+        for value in 1..=3 {
+            sender.send(value).unwrap();
+        }
+        assert_eq!(received(&receiver), [1, 2]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room() {
+        let (sender, receiver) = Channel::bounded(2, OverflowPolicy::DropOldest);
+        for value in 1..=3 {
+            sender.send(value).unwrap();
+        }
+        assert_eq!(received(&receiver), [2, 3]);
+    }
+
+    #[test]
+    fn error_returns_the_value() {
+        let (sender, receiver) = Channel::bounded(2, OverflowPolicy::Error);
+        sender.send(1).unwrap();
+        sender.send(2).unwrap();
+
+        let error = sender.send(3).unwrap_err();
+        assert!(error.is_full());
+        assert_eq!(error.into_inner(), 3);
+        assert_eq!(received(&receiver), [1, 2]);
+    }
+
+    #[test]
+    fn sending_to_a_closed_channel_fails() {
+        for policy in [
+            OverflowPolicy::Block,
+            OverflowPolicy::DropOldest,
+            OverflowPolicy::DropNewest,
+            OverflowPolicy::Error,
+        ] {
+            let (sender, receiver) = Channel::bounded(1, policy);
+            drop(receiver);
+            assert_eq!(sender.send(1), Err(SendError::Closed(1)));
+        }
     }
 }
@@ -0,0 +1,344 @@
+use gtk::glib;
+use std::panic::{AssertUnwindSafe, Location};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::failure::{self, Failure, FailureSource};
+
+/// Tells a background job that it should stop. The job has to check
+/// `is_cancelled` itself since a thread can't be stopped from outside.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+type JobThread<P, O> = Box<dyn FnOnce(&JobContext<P>) -> O + Send>;
+
+enum Message<P, O> {
+    Progress(P),
+    /// A coalesced progress update is waiting in the slot
+    ProgressReady,
+    Done(O),
+}
+
+/// Given to the thread of a `Job` to report progress and check if the job
+/// was cancelled
+pub struct JobContext<P> {
+    token: CancellationToken,
+    send: Box<dyn Fn(P) + Send>,
+}
+
+impl<P> JobContext<P> {
+    /// Sends a progress update to the main thread. Updates sent after the
+    /// job was cancelled are dropped.
+    pub fn progress(&self, progress: P) {
+        if !self.token.is_cancelled() {
+            (self.send)(progress);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+/// Runs a function in a background thread that reports progress of type
+/// `P` while it works and returns an output of type `O` when it's done.
+/// The callbacks are called on the main thread, so GObjects can be used in
+/// them. If the job is cancelled no more callbacks are called.
+///
+/// With `coalesce_progress` only the latest progress update is passed to
+/// `on_progress` when the main thread gets to it, so a thread that reports
+/// progress faster than it can be shown doesn't flood the main loop.
+///
+/// # Example
+/// ```
+/// let job = rose::Job::new(move |context: &rose::JobContext<f64>| {
+///     for (i, contact) in contacts.iter().enumerate() {
+///         if context.is_cancelled() {
+///             break;
+///         }
+///         import(contact);
+///         context.progress(i as f64 / contacts.len() as f64);
+///     }
+///     contacts.len()
+/// })
+/// .coalesce_progress(true)
+/// .on_progress(move |fraction| progress_bar.set_fraction(fraction))
+/// .on_complete(move |count| label.set_text(&format!("Imported {count} contacts")))
+/// .spawn();
+///
+/// cancel_button.connect_clicked(move |_| job.cancel());
+/// ```
+#[must_use = "The job must be spawned to run"]
+pub struct Job<P, O> {
+    thread: JobThread<P, O>,
+    on_progress: Option<Box<dyn Fn(P)>>,
+    on_complete: Option<Box<dyn FnOnce(O)>>,
+    coalesce: bool,
+}
+
+impl<P, O> Job<P, O>
+where
+    P: Send + 'static,
+    O: Send + 'static,
+{
+    pub fn new(thread: impl FnOnce(&JobContext<P>) -> O + Send + 'static) -> Self {
+        Self {
+            thread: Box::new(thread),
+            on_progress: None,
+            on_complete: None,
+            coalesce: false,
+        }
+    }
+
+    /// Called on the main thread for each progress update
+    pub fn on_progress(mut self, callback: impl Fn(P) + 'static) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Called on the main thread with the output of the job
+    pub fn on_complete(mut self, callback: impl FnOnce(O) + 'static) -> Self {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+
+    /// Only passes the latest progress update to `on_progress`
+    pub fn coalesce_progress(mut self, coalesce: bool) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    /// Starts the job in a new thread
+    #[track_caller]
+    pub fn spawn(self) -> JobHandle {
+        let location = Location::caller();
+        let token = CancellationToken::new();
+        let (sender, receiver) = async_channel::unbounded::<Message<P, O>>();
+        let slot = Arc::new(Mutex::new(None::<P>));
+
+        let send: Box<dyn Fn(P) + Send> = if self.coalesce {
+            let sender = sender.clone();
+            let slot = slot.clone();
+            Box::new(move |progress| {
+                // Only wake the main thread if it has taken the last update
+                let was_empty = slot.lock().unwrap().replace(progress).is_none();
+                if was_empty {
+                    let _ = sender.try_send(Message::ProgressReady);
+                }
+            })
+        } else {
+            let sender = sender.clone();
+            Box::new(move |progress| {
+                let _ = sender.try_send(Message::Progress(progress));
+            })
+        };
+        let context = JobContext {
+            token: token.clone(),
+            send,
+        };
+
+        let thread = self.thread;
+        std::thread::spawn(move || {
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| thread(&context)));
+            match result {
+                Ok(output) => {
+                    let _ = sender.try_send(Message::Done(output));
+                }
+                Err(payload) => failure::report_failure_from_thread(Failure::from_panic(
+                    payload,
+                    FailureSource::Task,
+                    Some(location),
+                )),
+            }
+            sender.close();
+        });
+
+        let on_progress = self.on_progress;
+        let mut on_complete = self.on_complete;
+        let is_cancelled = token.clone();
+        glib::spawn_future_local(async move {
+            let report = |progress: P| match &on_progress {
+                Some(on_progress) => failure::catch_callback(location, || on_progress(progress)),
+                None => Some(()),
+            };
+            while let Ok(message) = receiver.recv().await {
+                if is_cancelled.is_cancelled() {
+                    break;
+                }
+                let reported = match message {
+                    Message::Progress(progress) => report(progress),
+                    Message::ProgressReady => {
+                        let progress = slot.lock().unwrap().take();
+                        match progress {
+                            Some(progress) => report(progress),
+                            None => Some(()),
+                        }
+                    }
+                    Message::Done(output) => {
+                        // Show the last coalesced update before completing
+                        let last = slot.lock().unwrap().take();
+                        if let Some(progress) = last {
+                            report(progress);
+                        }
+                        match on_complete.take() {
+                            Some(on_complete) => {
+                                failure::catch_callback(location, || on_complete(output))
+                            }
+                            None => Some(()),
+                        }
+                    }
+                };
+                if reported.is_none() {
+                    break;
+                }
+            }
+        });
+
+        JobHandle { token }
+    }
+}
+
+/// A handle to a running `Job`
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    token: CancellationToken,
+}
+
+impl JobHandle {
+    /// Cancels the job. Its callbacks won't be called anymore and
+    /// `JobContext::is_cancelled` returns true in the thread.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    /// Runs the test with a new main context as the thread's default so the
+    /// job's callbacks are called when it's iterated
+    fn with_context(test: impl FnOnce(&glib::MainContext)) {
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| test(&context))
+            .expect("Failed to acquire the main context");
+    }
+
+    fn iterate_until(context: &glib::MainContext, done: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() && Instant::now() < deadline {
+            context.iteration(false);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn recorded_job(
+        coalesce: bool,
+        finished: mpsc::Sender<()>,
+    ) -> (Job<i32, i32>, Rc<RefCell<Vec<i32>>>, Rc<Cell<Option<i32>>>) {
+        let progress = Rc::new(RefCell::new(Vec::new()));
+        let output = Rc::new(Cell::new(None));
+        let (recorded, completed) = (progress.clone(), output.clone());
+        let job = Job::new(move |context: &JobContext<i32>| {
+            for i in 1..=100 {
+                context.progress(i);
+            }
+            finished.send(()).unwrap();
+            100
+        })
+        .coalesce_progress(coalesce)
+        .on_progress(move |i| recorded.borrow_mut().push(i))
+        .on_complete(move |count| completed.set(Some(count)));
+        (job, progress, output)
+    }
+
+    #[test]
+    fn every_progress_update_is_reported() {
+        with_context(|context| {
+            let (finished, thread_done) = mpsc::channel();
+            let (job, progress, output) = recorded_job(false, finished);
+            let _handle = job.spawn();
+
+            thread_done.recv().unwrap();
+            iterate_until(context, || output.get().is_some());
+            assert_eq!(*progress.borrow(), (1..=100).collect::<Vec<_>>());
+            assert_eq!(output.get(), Some(100));
+        });
+    }
+
+    #[test]
+    fn coalesced_progress_reports_the_latest_update() {
+        with_context(|context| {
+            let (finished, thread_done) = mpsc::channel();
+            let (job, progress, output) = recorded_job(true, finished);
+            let _handle = job.spawn();
+
+            // Every update is sent before the main context gets to them
+            thread_done.recv().unwrap();
+            iterate_until(context, || output.get().is_some());
+            assert_eq!(*progress.borrow(), [100]);
+            assert_eq!(output.get(), Some(100));
+        });
+    }
+
+    #[test]
+    fn cancelled_job_stops_and_does_not_complete() {
+        with_context(|context| {
+            let (started, thread_started) = mpsc::channel();
+            let (finished, thread_done) = mpsc::channel();
+            let completed = Rc::new(Cell::new(false));
+            let was_completed = completed.clone();
+            let handle = Job::new(move |context: &JobContext<()>| {
+                started.send(()).unwrap();
+                while !context.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                finished.send(context.is_cancelled()).unwrap();
+            })
+            .on_complete(move |_| was_completed.set(true))
+            .spawn();
+
+            thread_started.recv().unwrap();
+            handle.cancel();
+            assert!(handle.is_cancelled());
+            assert!(thread_done.recv().unwrap());
+
+            let deadline = Instant::now() + Duration::from_millis(100);
+            iterate_until(context, || Instant::now() > deadline);
+            assert!(!completed.get());
+        });
+    }
+}
@@ -1,2 +1,4 @@
 pub mod channels;
+pub mod job;
 pub mod spawn;
+pub mod task;
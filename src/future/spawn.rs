@@ -1,10 +1,26 @@
 use gtk::glib;
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::panic::{AssertUnwindSafe, Location};
+use std::rc::Rc;
 
+#[cfg(feature = "tokio")]
 use gtk::glib::once_cell;
-use tokio::runtime::Runtime;
 
-pub static RUNTIME: once_cell::sync::Lazy<Runtime> =
-    once_cell::sync::Lazy::new(|| Runtime::new().unwrap());
+use super::task::{ErrorDisplay, TaskError, TaskHandle, TaskStatus};
+use crate::failure::{self, Failure, FailureSource};
+use crate::prelude::*;
+use crate::runtime::{self, JoinError};
+use crate::{Application, DisplayableError};
+
+/// The tokio runtime futures were spawned on before `rose::runtime`. Futures
+/// are now spawned on the runtime from `rose::runtime::handle()`, so this
+/// is a separate runtime that is created the first time it's used and isn't
+/// shut down with the application.
+#[cfg(feature = "tokio")]
+#[deprecated(note = "Use `rose::runtime::handle()` instead")]
+pub static RUNTIME: once_cell::sync::Lazy<tokio::runtime::Runtime> =
+    once_cell::sync::Lazy::new(|| tokio::runtime::Runtime::new().unwrap());
 
 /// Spawn a future in the tokio runtime and call the callback in the glib main context.
 ///
@@ -33,17 +49,173 @@ pub static RUNTIME: once_cell::sync::Lazy<Runtime> =
 ///     label.set_text(&resp);
 /// });
 /// ```
+///
+/// If the future or the callback panics the panic is passed to the
+/// application's failure reporter instead of taking down the application.
+///
+/// The returned `TaskHandle` can cancel the task or be awaited to find out
+/// how it ended.
+#[track_caller]
 pub fn spawn_async<T>(
     future: impl std::future::Future<Output = T> + 'static + Send,
     callback: impl FnOnce(T) + 'static,
-) where
+) -> TaskHandle
+where
     T: Send + 'static,
 {
-    let handel = RUNTIME.spawn(future);
+    let location = Location::caller();
+    spawn_task(future, location, false, move |result| {
+        finish_task(result, location, callback)
+    })
+}
 
-    glib::spawn_future_local(async move {
-        callback(handel.await.unwrap());
+/// Like `spawn_async` but for work that must not be lost, like saving a
+/// file. The application is held while the task runs so it doesn't quit
+/// when its last window is closed, and shutdown waits for the task until
+/// the shutdown timeout.
+///
+/// # Example
+/// ```
+/// rose::spawn_critical(save_contact(contact), move |_| {
+///     window.set_title(Some("Saved"));
+/// });
+/// ```
+#[track_caller]
+pub fn spawn_critical<T>(
+    future: impl std::future::Future<Output = T> + 'static + Send,
+    callback: impl FnOnce(T) + 'static,
+) -> TaskHandle
+where
+    T: Send + 'static,
+{
+    let location = Location::caller();
+    let hold = Application::try_find().map(|app| app.hold());
+    spawn_task(future, location, true, move |result| {
+        let status = finish_task(result, location, callback);
+        drop(hold);
+        status
+    })
+}
+
+/// Calls the callback with the result of a task or reports its panic
+fn finish_task<T>(
+    result: Result<T, JoinError>,
+    location: &'static Location<'static>,
+    callback: impl FnOnce(T),
+) -> TaskStatus {
+    match result {
+        Ok(value) => match failure::catch_callback(location, || callback(value)) {
+            Some(()) => TaskStatus::Completed,
+            None => TaskStatus::Panicked,
+        },
+        Err(JoinError::Panic(payload)) => {
+            failure::report_failure(Failure::from_panic(
+                payload,
+                FailureSource::Task,
+                Some(location),
+            ));
+            TaskStatus::Panicked
+        }
+        Err(JoinError::Cancelled) => TaskStatus::Cancelled,
+    }
+}
+
+/// Spawn a future that can fail on the runtime. The callback is
+/// called in the glib main context if the future succeeds. Errors and
+/// tasks that were stopped by the runtime are shown on the router's error
+/// page as a `TaskError`. Panics are passed to the application's failure
+/// reporter like with `spawn_async`.
+///
+/// # Example
+/// ```
+/// rose::spawn_async_result(load_contact(id), move |contact| {
+///     label.set_text(&contact.name);
+/// });
+/// ```
+#[track_caller]
+pub fn spawn_async_result<T, E>(
+    future: impl std::future::Future<Output = Result<T, E>> + 'static + Send,
+    callback: impl FnOnce(T) + 'static,
+) -> TaskHandle
+where
+    T: Send + 'static,
+    E: DisplayableError + Send + 'static,
+{
+    spawn_async_result_with(future, ErrorDisplay::ErrorPage, callback)
+}
+
+/// Like `spawn_async_result` but chooses how errors are shown.
+///
+/// # Example
+/// ```
+/// rose::spawn_async_result_with(
+///     save_contact(contact),
+///     ErrorDisplay::Toast(button.clone().upcast()),
+///     move |_| button.set_sensitive(true),
+/// );
+/// ```
+#[track_caller]
+pub fn spawn_async_result_with<T, E>(
+    future: impl std::future::Future<Output = Result<T, E>> + 'static + Send,
+    display: ErrorDisplay,
+    callback: impl FnOnce(T) + 'static,
+) -> TaskHandle
+where
+    T: Send + 'static,
+    E: DisplayableError + Send + 'static,
+{
+    let location = Location::caller();
+    spawn_task(future, location, false, move |result| match result {
+        Ok(Ok(value)) => match failure::catch_callback(location, || callback(value)) {
+            Some(()) => TaskStatus::Completed,
+            None => TaskStatus::Panicked,
+        },
+        Ok(Err(error)) => {
+            display.show(&TaskError::Failed(error));
+            TaskStatus::Failed
+        }
+        Err(JoinError::Panic(payload)) => {
+            failure::report_failure(Failure::from_panic(
+                payload,
+                FailureSource::Task,
+                Some(location),
+            ));
+            TaskStatus::Panicked
+        }
+        Err(JoinError::Cancelled) => {
+            display.show(&TaskError::<E>::Cancelled);
+            TaskStatus::Cancelled
+        }
+    })
+}
+
+/// Spawns the future on the runtime and calls `finish` in the glib main
+/// context unless the task was cancelled with its handle
+fn spawn_task<T>(
+    future: impl std::future::Future<Output = T> + 'static + Send,
+    location: &'static Location<'static>,
+    critical: bool,
+    finish: impl FnOnce(Result<T, JoinError>) -> TaskStatus + 'static,
+) -> TaskHandle
+where
+    T: Send + 'static,
+{
+    let (handel, abort) = runtime::spawn(future, location, critical);
+    let cancelled = Rc::new(Cell::new(false));
+    let finished = Rc::new(Cell::new(false));
+
+    let (is_cancelled, is_finished) = (cancelled.clone(), finished.clone());
+    let local = glib::spawn_future_local(async move {
+        let result = handel.await;
+        // The future might finish before it's aborted
+        if is_cancelled.get() {
+            return TaskStatus::Cancelled;
+        }
+        is_finished.set(true);
+        finish(result)
     });
+
+    TaskHandle::new(abort, cancelled, finished, local)
 }
 
 /// Spawn a background thread that can send multiple values back to the main thread.
@@ -74,22 +246,33 @@ pub fn spawn_async<T>(
 ///    // This is called in the glib main context, so GObjects can be safely accessed here.
 ///   label.set_text(&resp);
 /// });
+#[track_caller]
 pub fn spawn_background_thread<T>(
     thread: impl FnOnce(async_channel::Sender<T>) -> T + Send + 'static,
     callback: impl Fn(T) + Send + 'static,
 ) where
     T: Send + 'static,
 {
+    let location = Location::caller();
     let (sender, receiver) = async_channel::bounded::<T>(1);
 
     std::thread::spawn(move || {
-        thread(sender.clone());
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| thread(sender.clone())));
         sender.close();
+        if let Err(payload) = result {
+            failure::report_failure_from_thread(Failure::from_panic(
+                payload,
+                FailureSource::Task,
+                Some(location),
+            ));
+        }
     });
 
     glib::spawn_future_local(async move {
         while let Ok(value) = receiver.recv().await {
-            callback(value);
+            if failure::catch_callback(location, || callback(value)).is_none() {
+                break;
+            }
         }
     });
 }
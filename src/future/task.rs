@@ -0,0 +1,160 @@
+use gtk::glib;
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use crate::application::actions::current_router;
+use crate::prelude::*;
+use crate::runtime::AbortHandle;
+use crate::{Application, DisplayableError};
+
+/// How a spawned task ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The future finished and the callback was called
+    Completed,
+    /// The future returned an error and the error was shown
+    Failed,
+    /// The task was cancelled before the callback was called
+    Cancelled,
+    /// The future or the callback panicked
+    Panicked,
+}
+
+/// A handle to a task spawned with `spawn_async`. Dropping the handle
+/// doesn't stop the task. The handle can be awaited on the main thread to
+/// find out how the task ended.
+///
+/// # Example
+/// ```
+/// let search = rose::spawn_async(search(query), move |results| {
+///     list.set_results(results);
+/// });
+///
+/// // The user typed again so the old results aren't needed anymore
+/// search.cancel();
+/// ```
+#[derive(Debug)]
+pub struct TaskHandle {
+    abort: Rc<AbortHandle>,
+    cancelled: Rc<Cell<bool>>,
+    /// Set when the future's result reached the main thread. After that
+    /// the task can't be cancelled anymore
+    finished: Rc<Cell<bool>>,
+    local: glib::JoinHandle<TaskStatus>,
+}
+
+impl TaskHandle {
+    pub(crate) fn new(
+        abort: AbortHandle,
+        cancelled: Rc<Cell<bool>>,
+        finished: Rc<Cell<bool>>,
+        local: glib::JoinHandle<TaskStatus>,
+    ) -> Self {
+        Self {
+            abort: Rc::new(abort),
+            cancelled,
+            finished,
+            local,
+        }
+    }
+
+    /// Aborts the future and makes sure the callback isn't called. This
+    /// does nothing if the callback was already called.
+    pub fn cancel(&self) {
+        cancel(&self.abort, &self.cancelled, &self.finished);
+    }
+
+    /// Gets a function that cancels the task. This can be kept after the
+    /// handle is awaited or dropped.
+    pub(crate) fn canceller(&self) -> impl Fn() + 'static {
+        let abort = self.abort.clone();
+        let cancelled = self.cancelled.clone();
+        let finished = self.finished.clone();
+        move || cancel(&abort, &cancelled, &finished)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+
+    /// Whether the future is done. The callback might not have been
+    /// called yet.
+    pub fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+}
+
+fn cancel(abort: &AbortHandle, cancelled: &Cell<bool>, finished: &Cell<bool>) {
+    if !finished.get() {
+        cancelled.set(true);
+        abort.abort();
+    }
+}
+
+impl Future for TaskHandle {
+    type Output = TaskStatus;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.local)
+            .poll(cx)
+            .map(|status| status.unwrap_or(TaskStatus::Cancelled))
+    }
+}
+
+/// Why a task spawned with `spawn_async_result` didn't succeed
+#[derive(Debug, Clone)]
+pub enum TaskError<E> {
+    /// The future returned an error
+    Failed(E),
+    /// The runtime stopped the task before it finished
+    Cancelled,
+}
+
+impl<E: DisplayableError> DisplayableError for TaskError<E> {
+    fn title(&self) -> String {
+        match self {
+            TaskError::Failed(error) => error.title(),
+            TaskError::Cancelled => crate::gettext("The task was stopped"),
+        }
+    }
+
+    fn body(&self) -> String {
+        match self {
+            TaskError::Failed(error) => error.body(),
+            TaskError::Cancelled => String::new(),
+        }
+    }
+}
+
+/// How `spawn_async_result_with` shows an error
+#[derive(Debug, Clone, Default)]
+pub enum ErrorDisplay {
+    /// Show the error on the router's error page
+    #[default]
+    ErrorPage,
+    /// Show the error's title in a toast in the widget's `adw::ToastOverlay`.
+    /// If the widget isn't in an overlay the error page is used instead.
+    Toast(gtk::Widget),
+}
+
+impl ErrorDisplay {
+    pub fn show(&self, error: &impl DisplayableError) {
+        match self {
+            ErrorDisplay::Toast(widget)
+                if widget.try_find_ancestor::<adw::ToastOverlay>().is_some() =>
+            {
+                widget.show_tost(&error.title(), 5, adw::ToastPriority::Normal);
+            }
+            _ => {
+                let router = Application::try_find().and_then(|app| current_router(&app));
+                match router {
+                    Some(router) => router.show_error(error),
+                    None => glib::g_warning!("rose", "Can't show error without a router"),
+                }
+            }
+        }
+    }
+}
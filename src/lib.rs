@@ -1,30 +1,96 @@
 mod application;
+mod event_bus;
+mod failure;
 mod functions;
 mod future;
 mod router;
+pub mod runtime;
+mod settings;
+mod state;
 mod utils;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
 pub use future::channels::Channel;
+pub use future::channels::OverflowPolicy;
 pub use future::channels::Receiver;
+pub use future::channels::SendError;
 pub use future::channels::Sender;
+pub use future::channels::Subscription;
+pub use future::job::CancellationToken;
+pub use future::job::Job;
+pub use future::job::JobContext;
+pub use future::job::JobHandle;
 pub use future::spawn::spawn_async;
+pub use future::spawn::spawn_async_result;
+pub use future::spawn::spawn_async_result_with;
 pub use future::spawn::spawn_background_thread;
+pub use future::spawn::spawn_critical;
+#[cfg(feature = "tokio")]
+#[allow(deprecated)]
 pub use future::spawn::RUNTIME;
+pub use future::task::ErrorDisplay;
+pub use future::task::TaskError;
+pub use future::task::TaskHandle;
+pub use future::task::TaskStatus;
 
+pub use application::actions::navigate_target;
+pub use application::actions::NAVIGATE_ACTION;
+pub use application::actions::NAVIGATE_NEW_WINDOW_ACTION;
+pub use application::builder::ApplicationBuilder;
+pub use application::command_line::OptionValue;
+pub use application::dependency::DependencyLifetime;
+pub use application::lifecycle::Lifecycle;
+pub use application::lifecycle::ShutdownFuture;
+pub use application::notification::DesktopNotificationBackend;
+pub use application::notification::NotificationBackend;
+pub use application::notification::NotificationButton;
+pub use application::notification::RouteNotification;
+pub use application::window_state::WindowState;
 pub use application::Application;
+pub use event_bus::EventBus;
+pub use event_bus::EventPublisher;
+pub use event_bus::EventSubscription;
+pub use failure::report_failure;
+pub use failure::CrashDialogReporter;
+pub use failure::ErrorPageReporter;
+pub use failure::Failure;
+pub use failure::FailureReporter;
+pub use failure::FailureSource;
 pub use router::error::DisplayableError;
+pub use router::history::HistoryEntry;
 pub use router::page_route::PageRoute;
 pub use router::Router;
+pub use settings::SettingsKey;
+pub use settings::TypedSettings;
+pub use state::State;
+pub use state::StateSender;
+pub use state::StateSubscription;
 
 pub use functions::add_dependency;
+pub use functions::add_dependency_named;
+pub use functions::add_singleton;
+pub use functions::add_transient;
+pub use functions::add_value_dependency;
 pub use functions::back;
 pub use functions::get_dependency;
+pub use functions::get_dependency_named;
+pub use functions::get_value_dependency;
+pub use functions::gettext;
+pub use functions::has_value_dependency;
 pub use functions::init;
+pub use functions::remove_dependency;
+pub use functions::replace_dependency;
+pub use functions::replace_dependency_as;
 pub use functions::show_error_page;
+pub use functions::try_get_value_dependency;
 pub use functions::visit;
 
 pub mod prelude {
     pub use crate::application::ext::RoseApplicationExt;
     pub use crate::router::ext::RoseRouterExt;
+    pub use crate::settings::TypedSettings;
     pub use crate::utils::variant::IsVariantTyVoidExt;
     pub use crate::utils::weak_ref::AsWeakRefExt;
     pub use crate::utils::widget::RoseWidgetExt;
@@ -39,3 +105,8 @@ pub mod subclass {
         pub use crate::subclass::router::*;
     }
 }
+
+#[doc(hidden)]
+pub mod __private {
+    pub use gtk::gio;
+}
@@ -5,6 +5,7 @@ use gtk::prelude::*;
 use gtk::{gio, glib};
 
 use crate::prelude::*;
+use crate::router::history::{self, HistoryEntry};
 use crate::{DisplayableError, PageRoute};
 
 pub trait RoseRouterExt:
@@ -31,17 +32,26 @@ pub trait RoseRouterExt:
         let imp = super::imp::Router::from_obj(self.upcast_ref());
         let nav_view = imp.view.borrow().clone();
         let static_page = if R::is_static() {
-            Some(R::build_page(None))
+            let page = R::build_page(None);
+            history::set_page_route(&page, R::route(), None);
+            imp.track_page(&page);
+            Some(page)
         } else {
             None
         };
 
+        let router = self.upcast_ref::<super::Router>().week_ref();
         action.connect_activate(move |_, parameter| {
             let page = if let Some(static_page) = static_page.clone() {
                 static_page
             } else {
                 let variant = parameter.and_then(|variant| variant.get::<R::Parameter>());
-                R::build_page(variant)
+                let page = R::build_page(variant);
+                history::set_page_route(&page, R::route(), parameter.cloned());
+                if let Some(router) = router.upgrade() {
+                    super::imp::Router::from_obj(&router).track_page(&page);
+                }
+                page
             };
 
             if R::is_top_level() {
@@ -83,6 +93,7 @@ pub trait RoseRouterExt:
             .build();
 
         let page = adw::NavigationPage::builder().child(&page).build();
+        history::mark_error_page(&page);
         let view = imp.view.borrow().clone();
         let current_page = match view.visible_page() {
             Some(page) => page,
@@ -105,10 +116,19 @@ pub trait RoseRouterExt:
     {
         let imp = super::imp::Router::from_obj(self.upcast_ref());
 
-        imp.action_group.activate_action(
-            &format!("visit.{}", R::route()),
-            parameter.map(|parameter| parameter.to_variant()).as_ref(),
-        );
+        // Routes that take `()` don't have a parameter on their action
+        let parameter = parameter
+            .map(|parameter| parameter.to_variant())
+            .filter(|parameter| !parameter.type_().is_void());
+        imp.action_group
+            .activate_action(&format!("visit.{}", R::route()), parameter.as_ref());
+    }
+
+    /// Checks if a route with the name has been added to the router
+    fn has_route(&self, route: &str) -> bool {
+        let imp = super::imp::Router::from_obj(self.upcast_ref());
+
+        imp.action_group.has_action(&format!("visit.{route}"))
     }
 
     // Navigates to a route. This will activate the action with the name `router.visit.<route>`
@@ -128,6 +148,37 @@ pub trait RoseRouterExt:
 
         imp.action_group.activate_action("back", None);
     }
+
+    /// Gets the pages on the navigation stack from the root page to the
+    /// visible page. This is useful for building breadcrumbs.
+    fn history(&self) -> Vec<HistoryEntry> {
+        let imp = super::imp::Router::from_obj(self.upcast_ref());
+
+        imp.view
+            .borrow()
+            .navigation_stack()
+            .iter::<adw::NavigationPage>()
+            .filter_map(Result::ok)
+            .map(HistoryEntry::from_page)
+            .collect()
+    }
+
+    /// Calls the callback when a page is pushed or popped or when the
+    /// title of a page on the navigation stack changes.
+    fn connect_history_changed<F>(&self, callback: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self) + 'static,
+    {
+        self.connect_local("history-changed", false, move |values| {
+            let this = values[0]
+                .get::<glib::Object>()
+                .expect("Failed to get router")
+                .downcast::<Self>()
+                .expect("Failed to downcast router");
+            callback(&this);
+            None
+        })
+    }
 }
 
 impl<T> RoseRouterExt for T where
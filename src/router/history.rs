@@ -0,0 +1,75 @@
+use adw::prelude::*;
+use gtk::glib;
+
+const ROUTE_KEY: &str = "rose-route";
+const PARAMETER_KEY: &str = "rose-route-parameter";
+const ERROR_KEY: &str = "rose-error-page";
+
+/// A single page in the router's navigation stack. This can be used to
+/// build breadcrumbs or a history menu.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    /// The route the page was built from. This is `None` for pages that
+    /// were not built by a route like the error page
+    pub route: Option<String>,
+    /// The parameter the page was built with
+    pub parameter: Option<glib::Variant>,
+    /// The current title of the page
+    pub title: glib::GString,
+    /// The navigation page itself
+    pub page: adw::NavigationPage,
+}
+
+impl HistoryEntry {
+    pub(crate) fn from_page(page: adw::NavigationPage) -> Self {
+        Self {
+            route: page_route(&page),
+            parameter: page_parameter(&page),
+            title: page.title(),
+            page,
+        }
+    }
+}
+
+/// Remembers the route and parameter a page was built from. We can't use
+/// the pages tag for this since libadwaita requires tags to be unique and
+/// the same route can be on the stack more than once
+pub(crate) fn set_page_route(
+    page: &adw::NavigationPage,
+    route: &str,
+    parameter: Option<glib::Variant>,
+) {
+    unsafe {
+        page.set_data(ROUTE_KEY, route.to_string());
+        page.set_data(PARAMETER_KEY, parameter);
+    }
+}
+
+/// Gets the route a page was built from
+pub(crate) fn page_route(page: &adw::NavigationPage) -> Option<String> {
+    unsafe {
+        page.data::<String>(ROUTE_KEY)
+            .map(|route| route.as_ref().clone())
+    }
+}
+
+/// Gets the parameter a page was built with
+pub(crate) fn page_parameter(page: &adw::NavigationPage) -> Option<glib::Variant> {
+    unsafe {
+        page.data::<Option<glib::Variant>>(PARAMETER_KEY)
+            .and_then(|parameter| parameter.as_ref().clone())
+    }
+}
+
+/// Marks a page as the router's error page
+pub(crate) fn mark_error_page(page: &adw::NavigationPage) {
+    unsafe {
+        page.set_data(ERROR_KEY, true);
+    }
+}
+
+/// Checks if a page is the router's error page
+#[cfg(feature = "testing")]
+pub(crate) fn is_error_page(page: &adw::NavigationPage) -> bool {
+    unsafe { page.data::<bool>(ERROR_KEY).is_some() }
+}
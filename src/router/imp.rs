@@ -2,8 +2,9 @@ use crate::prelude::*;
 use crate::router::ext::RoseRouterExt;
 use adw::prelude::*;
 use adw::subclass::prelude::*;
+use glib::subclass::Signal;
 use gtk::gio;
-use gtk::glib::{self};
+use gtk::glib::{self, once_cell::sync::Lazy};
 use std::cell::{Cell, RefCell};
 
 use crate::Application;
@@ -27,8 +28,27 @@ impl ObjectSubclass for Router {
     type ParentType = adw::Bin;
 }
 
+impl Router {
+    /// Emits `history-changed` whenever the title of `page` changes so
+    /// breadcrumbs stay up to date when a page loads its title async
+    pub fn track_page(&self, page: &adw::NavigationPage) {
+        let obj = self.obj().week_ref();
+        page.connect_title_notify(move |_| {
+            if let Some(this) = obj.upgrade() {
+                this.emit_by_name::<()>("history-changed", &[]);
+            }
+        });
+    }
+}
+
 #[glib::derived_properties]
 impl ObjectImpl for Router {
+    fn signals() -> &'static [Signal] {
+        static SIGNALS: Lazy<Vec<Signal>> =
+            Lazy::new(|| vec![Signal::builder("history-changed").build()]);
+        SIGNALS.as_ref()
+    }
+
     fn constructed(&self) {
         self.parent_constructed();
         self.obj()
@@ -53,6 +73,24 @@ impl ObjectImpl for Router {
                 this.set_can_go_back(nav_view.previous_page(&page).is_some());
             });
 
+        // Let breadcrumbs know when pages are added or removed
+        let view = self.view.borrow().clone();
+        let obj = self.obj().week_ref();
+        view.connect_pushed(move |_| {
+            let this = obj.upgrade().unwrap();
+            this.emit_by_name::<()>("history-changed", &[]);
+        });
+        let obj = self.obj().week_ref();
+        view.connect_popped(move |_, _| {
+            let this = obj.upgrade().unwrap();
+            this.emit_by_name::<()>("history-changed", &[]);
+        });
+        let obj = self.obj().week_ref();
+        view.connect_replaced(move |_| {
+            let this = obj.upgrade().unwrap();
+            this.emit_by_name::<()>("history-changed", &[]);
+        });
+
         // gtk gets angy when the view stack dose not have a parent
         // so we set the parent to the router evan tho it wont
         // actually be displayed
@@ -5,6 +5,7 @@ pub mod imp;
 pub mod subclass;
 pub mod page_route;
 pub mod error;
+pub mod history;
 
 
 glib::wrapper! {
@@ -31,6 +31,25 @@ where
         false
     }
 
+    /// The title of the page that will be shown in the header bar and in
+    /// the router's history. By default this is the route but it should
+    /// be overridden with a human readable title. The parameter can be
+    /// used to build titles like a contact's name. Titles are user facing
+    /// so they should be translated with `rose::gettext`
+    ///
+    /// # Example
+    /// ```
+    /// fn title(parameter: Option<&Self::Parameter>) -> String {
+    ///     match parameter {
+    ///         Some(name) => name.clone(),
+    ///         None => rose::gettext("Contact"),
+    ///     }
+    /// }
+    /// ```
+    fn title(_parameter: Option<&Self::Parameter>) -> String {
+        Self::route().to_string()
+    }
+
     /// This function should build the Widget that will be
     /// displayed when the route is navigated to
     fn build(parameter: Option<Self::Parameter>) -> impl IsA<gtk::Widget>;
@@ -38,12 +57,12 @@ where
     /// This function should build the navigation page that will be
     /// added to the view stack when the route is navigated to.
     /// By default this will just build a navigation page with the
-    /// title set to the route and the child set to the result of
+    /// title set to `Self::title` and the child set to the result of
     /// `Self::build` but this can be overridden if you need to
     /// customize the navigation page in some way
     fn build_page(parameter: Option<Self::Parameter>) -> adw::NavigationPage {
         adw::NavigationPage::builder()
-            .title(Self::route())
+            .title(Self::title(parameter.as_ref()))
             .child(&Self::build(parameter))
             .build()
     }
@@ -0,0 +1,385 @@
+//! The executor futures spawned with `rose::spawn_async` run on.
+//!
+//! With the `tokio` feature (on by default) futures run on a tokio runtime.
+//! The runtime is created with `Runtime::new()` the first time a future is
+//! spawned unless the app configures it first with `configure` or shares
+//! its own runtime with `use_handle`.
+//!
+//! When the application shuts down `shutdown_requested` resolves so tasks
+//! can stop early. The application waits for running tasks until its
+//! shutdown timeout and then aborts and reports the tasks that are left.
+//! A runtime rose created is shut down with the application, a runtime
+//! shared with `use_handle` is left to the app.
+//!
+//! Without the `tokio` feature futures run on a glib main context in a
+//! single background thread. This is enough for futures that wait on gio
+//! or channels but futures that need tokio's IO or time drivers won't work.
+
+use gtk::glib;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use glib::once_cell::sync::Lazy;
+
+/// Why the runtime couldn't be configured
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// The runtime was already configured or a future was already spawned
+    AlreadyStarted,
+    /// The runtime couldn't be built
+    Build(std::io::Error),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::AlreadyStarted => write!(f, "The runtime was already started"),
+            RuntimeError::Build(error) => write!(f, "Failed to build the runtime: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[cfg(feature = "tokio")]
+enum Executor {
+    Owned(tokio::runtime::Runtime),
+    Shared(tokio::runtime::Handle),
+}
+
+#[cfg(not(feature = "tokio"))]
+struct Executor {
+    context: glib::MainContext,
+    main_loop: glib::MainLoop,
+}
+
+/// The executor is created when the first future is spawned and taken
+/// when the application shuts down
+static EXECUTOR: Mutex<Option<Executor>> = Mutex::new(None);
+
+/// Builds the runtime rose spawns futures on. This must be called before
+/// the first future is spawned.
+///
+/// # Example
+/// ```
+/// let mut builder = tokio::runtime::Builder::new_multi_thread();
+/// builder.worker_threads(2).thread_name("contacts-worker").enable_all();
+/// rose::runtime::configure(builder)?;
+/// ```
+#[cfg(feature = "tokio")]
+pub fn configure(mut builder: tokio::runtime::Builder) -> Result<(), RuntimeError> {
+    let mut executor = EXECUTOR.lock().unwrap();
+    if executor.is_some() {
+        return Err(RuntimeError::AlreadyStarted);
+    }
+    let runtime = builder.build().map_err(RuntimeError::Build)?;
+    *executor = Some(Executor::Owned(runtime));
+    Ok(())
+}
+
+/// Spawns futures on a runtime the app already has instead of creating
+/// one. This must be called before the first future is spawned.
+///
+/// # Example
+/// ```
+/// #[tokio::main]
+/// async fn main() {
+///     rose::runtime::use_handle(tokio::runtime::Handle::current())?;
+///     ...
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub fn use_handle(handle: tokio::runtime::Handle) -> Result<(), RuntimeError> {
+    let mut executor = EXECUTOR.lock().unwrap();
+    if executor.is_some() {
+        return Err(RuntimeError::AlreadyStarted);
+    }
+    *executor = Some(Executor::Shared(handle));
+    Ok(())
+}
+
+/// Gets a handle to the runtime, creating it if it wasn't configured
+#[cfg(feature = "tokio")]
+pub fn handle() -> tokio::runtime::Handle {
+    with_executor(|executor| match executor {
+        Executor::Owned(runtime) => runtime.handle().clone(),
+        Executor::Shared(handle) => handle.clone(),
+    })
+}
+
+/// Calls `f` with the executor, creating it if it doesn't exist yet
+fn with_executor<R>(f: impl FnOnce(&Executor) -> R) -> R {
+    let mut executor = EXECUTOR.lock().unwrap();
+    f(executor.get_or_insert_with(Executor::new))
+}
+
+#[cfg(feature = "tokio")]
+impl Executor {
+    fn new() -> Self {
+        Executor::Owned(tokio::runtime::Runtime::new().expect("Failed to create tokio runtime"))
+    }
+
+    /// Shuts down a runtime rose created without waiting for the tasks
+    /// that are left
+    fn shutdown(self) {
+        if let Executor::Owned(runtime) = self {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Executor {
+    fn new() -> Self {
+        let context = glib::MainContext::new();
+        let main_loop = glib::MainLoop::new(Some(&context), false);
+        let thread_loop = main_loop.clone();
+        std::thread::Builder::new()
+            .name("rose-executor".to_string())
+            .spawn(move || {
+                thread_loop
+                    .context()
+                    .with_thread_default(|| thread_loop.run())
+                    .expect("Failed to acquire the executor's main context");
+            })
+            .expect("Failed to start the executor thread");
+        Executor { context, main_loop }
+    }
+
+    /// Stops the executor thread
+    fn shutdown(self) {
+        self.main_loop.quit();
+    }
+}
+
+/// A task that is registered so shutdown can wait for it
+struct RunningTask {
+    location: &'static Location<'static>,
+    critical: bool,
+    abort: Option<AbortHandle>,
+}
+
+static RUNNING: Lazy<Mutex<HashMap<u64, RunningTask>>> = Lazy::new(Mutex::default);
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(0);
+
+type ShutdownSignal = (async_channel::Sender<()>, async_channel::Receiver<()>);
+
+/// Closed when the application shuts down. Closing wakes every task that
+/// waits on the receiver. A new signal is made when an application starts
+/// so an application that is run again isn't shutting down.
+static SHUTDOWN: Lazy<Mutex<ShutdownSignal>> = Lazy::new(|| Mutex::new(async_channel::bounded(1)));
+
+/// Removes a task from `RUNNING` when its future is dropped, which happens
+/// when it finishes, panics or is aborted
+struct RunningGuard(u64);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Whether the application has started shutting down
+pub fn is_shutting_down() -> bool {
+    SHUTDOWN.lock().unwrap().0.is_closed()
+}
+
+/// Resolves when the application starts shutting down. Long running tasks
+/// can race their work against this to stop early and save their state.
+///
+/// # Example
+/// ```
+/// rose::spawn_async(async move {
+///     tokio::select! {
+///         _ = sync.run() => {}
+///         _ = rose::runtime::shutdown_requested() => sync.save_progress().await,
+///     }
+/// }, |_| {});
+/// ```
+pub async fn shutdown_requested() {
+    let receiver = SHUTDOWN.lock().unwrap().1.clone();
+    let _ = receiver.recv().await;
+}
+
+/// Resets the shutdown signal if an earlier application shut down. This is
+/// called by `Application` when it starts up.
+pub(crate) fn start() {
+    let mut signal = SHUTDOWN.lock().unwrap();
+    if signal.0.is_closed() {
+        *signal = async_channel::bounded(1);
+    }
+}
+
+/// Signals cancellation to tasks and waits for them until the timeout.
+/// Tasks that are still running after the timeout are aborted and
+/// reported. This is called by `Application` when it shuts down.
+pub(crate) async fn shutdown(timeout: Duration) {
+    SHUTDOWN.lock().unwrap().0.close();
+
+    let deadline = Instant::now() + timeout;
+    while !RUNNING.lock().unwrap().is_empty() && Instant::now() < deadline {
+        glib::timeout_future(Duration::from_millis(20)).await;
+    }
+
+    let unfinished: Vec<_> = RUNNING
+        .lock()
+        .unwrap()
+        .drain()
+        .map(|(_, task)| task)
+        .collect();
+    for task in unfinished {
+        let kind = if task.critical {
+            "Critical task"
+        } else {
+            "Task"
+        };
+        glib::g_warning!(
+            "rose",
+            "{kind} spawned at {} didn't finish before shutdown",
+            task.location
+        );
+        if let Some(abort) = task.abort {
+            abort.abort();
+        }
+    }
+
+    // A future spawned after this creates a new executor
+    let executor = EXECUTOR.lock().unwrap().take();
+    if let Some(executor) = executor {
+        executor.shutdown();
+    }
+}
+
+/// Why a spawned future didn't finish
+pub(crate) enum JoinError {
+    Panic(Box<dyn Any + Send + 'static>),
+    Cancelled,
+}
+
+/// Waits for a future spawned with `spawn`
+pub(crate) struct JoinHandle<T> {
+    #[cfg(feature = "tokio")]
+    inner: tokio::task::JoinHandle<T>,
+    #[cfg(not(feature = "tokio"))]
+    inner: glib::JoinHandle<T>,
+}
+
+/// Stops a future spawned with `spawn`
+#[derive(Debug)]
+pub(crate) struct AbortHandle {
+    #[cfg(feature = "tokio")]
+    inner: tokio::task::AbortHandle,
+    #[cfg(not(feature = "tokio"))]
+    inner: glib::Source,
+}
+
+impl AbortHandle {
+    pub fn abort(&self) {
+        #[cfg(feature = "tokio")]
+        self.inner.abort();
+        #[cfg(not(feature = "tokio"))]
+        self.inner.destroy();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        #[cfg(feature = "tokio")]
+        return self.inner.is_finished();
+        #[cfg(not(feature = "tokio"))]
+        return self.inner.is_destroyed();
+    }
+}
+
+impl<T> JoinHandle<T> {
+    fn abort_handle(&self) -> AbortHandle {
+        #[cfg(feature = "tokio")]
+        return AbortHandle {
+            inner: self.inner.abort_handle(),
+        };
+        #[cfg(not(feature = "tokio"))]
+        return AbortHandle {
+            inner: self.inner.source().clone(),
+        };
+    }
+}
+
+/// Spawns a future on the executor. The task is registered so shutdown
+/// can wait for it and report it with its location if it doesn't finish.
+pub(crate) fn spawn<T>(
+    future: impl Future<Output = T> + Send + 'static,
+    location: &'static Location<'static>,
+    critical: bool,
+) -> (JoinHandle<T>, AbortHandle)
+where
+    T: Send + 'static,
+{
+    let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+    RUNNING.lock().unwrap().insert(
+        id,
+        RunningTask {
+            location,
+            critical,
+            abort: None,
+        },
+    );
+    // The guard is made before the future so a task that is aborted
+    // before it's first polled still removes itself
+    let guard = RunningGuard(id);
+    let future = async move {
+        let _guard = guard;
+        future.await
+    };
+
+    #[cfg(feature = "tokio")]
+    let inner = with_executor(|executor| match executor {
+        Executor::Owned(runtime) => runtime.spawn(future),
+        Executor::Shared(handle) => handle.spawn(future),
+    });
+    #[cfg(not(feature = "tokio"))]
+    let inner = with_executor(|executor| executor.context.spawn(future));
+
+    let handle = JoinHandle { inner };
+    // The task might have finished already and removed itself
+    if let Some(task) = RUNNING.lock().unwrap().get_mut(&id) {
+        task.abort = Some(handle.abort_handle());
+    }
+    let abort = handle.abort_handle();
+    (handle, abort)
+}
+
+impl<T: 'static> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx).map(|result| {
+            result.map_err(|error| match error.try_into_panic() {
+                Ok(payload) => JoinError::Panic(payload),
+                Err(_) => JoinError::Cancelled,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aborted_tasks_are_removed_before_they_run() {
+        let (_handle, abort) = spawn(std::future::pending::<()>(), Location::caller(), false);
+        abort.abort();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !RUNNING.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(RUNNING.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use gtk::gio::{self, prelude::*};
+use gtk::glib::{self, IsA};
+use std::marker::PhantomData;
+
+/// A key in a settings schema with the type of its value
+pub struct SettingsKey<T> {
+    name: &'static str,
+    value_type: PhantomData<fn() -> T>,
+}
+
+impl<T> SettingsKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value_type: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for SettingsKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SettingsKey<T> {}
+
+impl<T> std::fmt::Debug for SettingsKey<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SettingsKey").field(&self.name).finish()
+    }
+}
+
+/// A typed wrapper around `gio::Settings`. This is usually implemented
+/// with the `rose::settings!` macro.
+pub trait TypedSettings: Clone + 'static {
+    /// The id of the schema the settings are for
+    const SCHEMA_ID: &'static str;
+
+    fn from_settings(settings: gio::Settings) -> Self;
+
+    fn settings(&self) -> &gio::Settings;
+
+    /// Creates the settings with the installed schema
+    fn new() -> Self {
+        Self::from_settings(gio::Settings::new(Self::SCHEMA_ID))
+    }
+
+    /// Creates the settings with a schema from `source` and stores the
+    /// values with `backend`. Using `gio::memory_settings_backend_new()`
+    /// and a source made from a folder of compiled schemas lets tests use
+    /// the settings without installing the schema.
+    fn with_backend(
+        source: &gio::SettingsSchemaSource,
+        backend: &impl IsA<gio::SettingsBackend>,
+    ) -> Self {
+        let schema = source
+            .lookup(Self::SCHEMA_ID, true)
+            .unwrap_or_else(|| panic!("Failed to find settings schema {}", Self::SCHEMA_ID));
+        Self::from_settings(gio::Settings::new_full(&schema, Some(backend), None))
+    }
+
+    /// Gets the value of a key
+    fn get<T: glib::FromVariant>(&self, key: SettingsKey<T>) -> T {
+        self.settings().get(key.name())
+    }
+
+    /// Sets the value of a key. This fails if the key isn't writable
+    fn set<T: glib::ToVariant>(
+        &self,
+        key: SettingsKey<T>,
+        value: T,
+    ) -> Result<(), glib::BoolError> {
+        self.settings().set(key.name(), value.to_variant())
+    }
+
+    /// Calls the callback with the new value whenever the key changes
+    fn connect_changed<T, F>(&self, key: SettingsKey<T>, callback: F) -> glib::SignalHandlerId
+    where
+        T: glib::FromVariant,
+        F: Fn(&Self, T) + 'static,
+    {
+        let this = self.clone();
+        self.settings()
+            .connect_changed(Some(key.name()), move |settings, key| {
+                callback(&this, settings.get(key));
+            })
+    }
+
+    /// Binds a key to a property of an object like a label's text or a
+    /// switch's active state. By default changes go both ways.
+    ///
+    /// # Example
+    /// ```
+    /// settings
+    ///     .bind(AppSettings::DARK_MODE, &switch, "active")
+    ///     .build();
+    /// ```
+    fn bind<'a, T>(
+        &'a self,
+        key: SettingsKey<T>,
+        object: &'a impl IsA<glib::Object>,
+        property: &'a str,
+    ) -> gio::BindingBuilder<'a> {
+        self.settings().bind(key.name(), object, property)
+    }
+}
+
+/// Declares a typed settings struct for a schema. The struct implements
+/// `TypedSettings` and has a `SettingsKey` constant for each key.
+///
+/// The macro doesn't generate a getter and setter for each key. Values are
+/// read, written and watched by passing the key constants to
+/// `TypedSettings::get`, `set`, `connect_changed` and `bind`, which check
+/// the value type at compile time.
+///
+/// # Example
+/// ```
+/// rose::settings! {
+///     pub struct AppSettings("com.example.App") {
+///         pub const DARK_MODE: bool = "dark-mode";
+///         pub const RECENT_SEARCHES: Vec<String> = "recent-searches";
+///     }
+/// }
+///
+/// let settings = AppSettings::new();
+/// settings.set(AppSettings::DARK_MODE, true)?;
+/// settings.connect_changed(AppSettings::DARK_MODE, |_, dark_mode| {
+///     println!("dark mode: {dark_mode}");
+/// });
+/// app.add_settings(settings);
+/// ```
+#[macro_export]
+macro_rules! settings {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($schema_id:literal) {
+            $(
+                $(#[$key_meta:meta])*
+                $key_vis:vis const $key:ident: $type:ty = $key_name:literal;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis struct $name($crate::__private::gio::Settings);
+
+        impl $name {
+            $(
+                $(#[$key_meta])*
+                $key_vis const $key: $crate::SettingsKey<$type> = $crate::SettingsKey::new($key_name);
+            )*
+        }
+
+        impl $crate::TypedSettings for $name {
+            const SCHEMA_ID: &'static str = $schema_id;
+
+            fn from_settings(settings: $crate::__private::gio::Settings) -> Self {
+                Self(settings)
+            }
+
+            fn settings(&self) -> &$crate::__private::gio::Settings {
+                &self.0
+            }
+        }
+    };
+}
@@ -0,0 +1,381 @@
+use gtk::glib::{self, prelude::*};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::Location;
+use std::rc::Rc;
+
+use crate::failure;
+use crate::{Channel, SendError, Sender, Subscription};
+
+type Listener<T> = Rc<dyn Fn(&T)>;
+
+struct Inner<T> {
+    value: RefCell<T>,
+    listeners: RefCell<Vec<(u64, Listener<T>)>>,
+    next_id: Cell<u64>,
+    /// The sender for updates from other threads and what delivers them
+    updates: RefCell<Option<(Sender<T>, Subscription)>>,
+    /// Keeps the states a derived state is computed from subscribed
+    sources: RefCell<Vec<Box<dyn Any>>>,
+}
+
+/// A value on the main thread that calls its subscribers when it changes.
+/// Cloning the state gives another handle to the same value.
+///
+/// # Example
+/// ```
+/// let query = rose::State::new(String::new());
+/// let has_query = query.map(|query| !query.is_empty());
+///
+/// query.bind_property(&label, "label");
+/// has_query.bind_property(&search_button, "sensitive");
+///
+/// entry.connect_changed(move |entry| query.set(entry.text().to_string()));
+/// ```
+pub struct State<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for State<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("State")
+            .field(&self.inner.value.borrow())
+            .finish()
+    }
+}
+
+impl<T: Default + Clone + 'static> Default for State<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Clone + 'static> State<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                value: RefCell::new(value),
+                listeners: RefCell::default(),
+                next_id: Cell::new(0),
+                updates: RefCell::default(),
+                sources: RefCell::default(),
+            }),
+        }
+    }
+
+    /// Gets a copy of the value
+    pub fn get(&self) -> T {
+        self.inner.value.borrow().clone()
+    }
+
+    /// Reads the value without copying it
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.inner.value.borrow())
+    }
+
+    /// Sets the value and calls the subscribers
+    pub fn set(&self, value: T) {
+        self.inner.value.replace(value);
+        self.notify();
+    }
+
+    /// Changes the value in place and calls the subscribers
+    ///
+    /// # Example
+    /// ```
+    /// selected.update(|ids| ids.push(contact.id()));
+    /// ```
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        f(&mut self.inner.value.borrow_mut());
+        self.notify();
+    }
+
+    /// Calls the callback with the new value whenever the value changes
+    /// until the returned subscription is dropped
+    #[track_caller]
+    pub fn subscribe(&self, callback: impl Fn(&T) + 'static) -> StateSubscription {
+        let location = Location::caller();
+        let id = self.add_listener(Rc::new(move |value| {
+            failure::catch_callback(location, || callback(value));
+        }));
+        let inner = Rc::downgrade(&self.inner);
+        StateSubscription {
+            remove: Some(Box::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    remove_listener(&inner, id);
+                }
+            })),
+        }
+    }
+
+    /// Creates a state that is computed from this one and changes with it
+    pub fn map<U: Clone + 'static>(&self, f: impl Fn(&T) -> U + 'static) -> State<U> {
+        let derived = State::new(self.with(&f));
+        let target = Rc::downgrade(&derived.inner);
+        let subscription = self.subscribe(move |value| {
+            if let Some(inner) = target.upgrade() {
+                State { inner }.set(f(value));
+            }
+        });
+        derived.keep_source(self.clone(), subscription);
+        derived
+    }
+
+    /// Creates a state that is computed from two states and changes with
+    /// either of them
+    ///
+    /// # Example
+    /// ```
+    /// let can_save = State::combine(&name, &is_saving, |name, is_saving| {
+    ///     !name.is_empty() && !is_saving
+    /// });
+    /// can_save.bind_property(&save_button, "sensitive");
+    /// ```
+    pub fn combine<A, B>(a: &State<A>, b: &State<B>, f: impl Fn(&A, &B) -> T + 'static) -> Self
+    where
+        A: Clone + 'static,
+        B: Clone + 'static,
+    {
+        let f = Rc::new(f);
+        let derived = State::new(a.with(|a| b.with(|b| f(a, b))));
+
+        let target = Rc::downgrade(&derived.inner);
+        let (other, compute) = (b.clone(), f.clone());
+        let subscription = a.subscribe(move |a| {
+            if let Some(inner) = target.upgrade() {
+                State { inner }.set(other.with(|b| compute(a, b)));
+            }
+        });
+        derived.keep_source(a.clone(), subscription);
+
+        let target = Rc::downgrade(&derived.inner);
+        let other = a.clone();
+        let subscription = b.subscribe(move |b| {
+            if let Some(inner) = target.upgrade() {
+                State { inner }.set(other.with(|a| f(a, b)));
+            }
+        });
+        derived.keep_source(b.clone(), subscription);
+
+        derived
+    }
+
+    /// Sets a property of an object to the value now and whenever it
+    /// changes. Only a weak reference to the object is kept and the
+    /// binding ends when the object is disposed.
+    ///
+    /// # Example
+    /// ```
+    /// let count = rose::State::new(0);
+    /// count.map(|count| format!("{count} contacts")).bind_property(&label, "label");
+    /// ```
+    pub fn bind_property(&self, object: &impl IsA<glib::Object>, property: &str)
+    where
+        T: ToValue,
+    {
+        let object = object.upcast_ref::<glib::Object>();
+        object.set_property(property, self.with(|value| value.to_value()));
+
+        let object_ref = object.downgrade();
+        let property = property.to_string();
+        let subscription = self.subscribe(move |value| {
+            if let Some(object) = object_ref.upgrade() {
+                object.set_property(&property, value.to_value());
+            }
+        });
+        object.add_weak_ref_notify_local(move || drop(subscription));
+    }
+
+    /// Gets a sender that can set the value from background tasks and
+    /// threads. The new values are set on the main thread.
+    ///
+    /// # Example
+    /// ```
+    /// let progress = rose::State::new(0.0);
+    /// let sender = progress.sender();
+    /// rose::spawn_async(async move {
+    ///     for i in 0..10 {
+    ///         download_part(i).await;
+    ///         sender.set(i as f64 / 10.0).ok();
+    ///     }
+    /// }, |_| {});
+    /// ```
+    pub fn sender(&self) -> StateSender<T>
+    where
+        T: Send,
+    {
+        let mut updates = self.inner.updates.borrow_mut();
+        let (sender, _) = updates.get_or_insert_with(|| {
+            let (sender, receiver) = Channel::new::<T>();
+            let inner = Rc::downgrade(&self.inner);
+            let subscription = receiver.attach(move |value| {
+                if let Some(inner) = inner.upgrade() {
+                    State { inner }.set(value);
+                }
+            });
+            (sender, subscription)
+        });
+        StateSender {
+            sender: sender.clone(),
+        }
+    }
+
+    fn notify(&self) {
+        let value = self.get();
+        // Clone the listeners so they can subscribe and set other states
+        let listeners: Vec<_> = self
+            .inner
+            .listeners
+            .borrow()
+            .iter()
+            .map(|(_, listener)| listener.clone())
+            .collect();
+        for listener in listeners {
+            listener(&value);
+        }
+    }
+
+    fn add_listener(&self, listener: Listener<T>) -> u64 {
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        self.inner.listeners.borrow_mut().push((id, listener));
+        id
+    }
+
+    fn keep_source<S: 'static>(&self, source: State<S>, subscription: StateSubscription) {
+        self.inner
+            .sources
+            .borrow_mut()
+            .push(Box::new((source, subscription)));
+    }
+}
+
+fn remove_listener<T>(inner: &Inner<T>, id: u64) {
+    inner
+        .listeners
+        .borrow_mut()
+        .retain(|(listener_id, _)| *listener_id != id);
+}
+
+/// Sets a `State` from any thread
+pub struct StateSender<T> {
+    sender: Sender<T>,
+}
+
+impl<T> Clone for StateSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> StateSender<T> {
+    /// Sets the value on the main thread. This fails if the state was
+    /// dropped.
+    pub fn set(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.send(value)
+    }
+}
+
+/// Keeps a callback subscribed to a `State`. The callback is unsubscribed
+/// when the subscription is dropped.
+#[must_use = "The callback is unsubscribed when the subscription is dropped"]
+pub struct StateSubscription {
+    remove: Option<Box<dyn FnOnce()>>,
+}
+
+impl StateSubscription {
+    /// Keeps the callback subscribed for as long as the state exists
+    pub fn forget(mut self) {
+        self.remove.take();
+    }
+}
+
+impl Drop for StateSubscription {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove.take() {
+            remove();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Subscribes to the state and records every value it's called with
+    fn record<T: Clone + 'static>(state: &State<T>) -> (Rc<RefCell<Vec<T>>>, StateSubscription) {
+        let values = Rc::new(RefCell::new(Vec::new()));
+        let recorded = values.clone();
+        let subscription = state.subscribe(move |value| recorded.borrow_mut().push(value.clone()));
+        (values, subscription)
+    }
+
+    #[test]
+    fn subscribers_are_called_once_per_change() {
+        let state = State::new(0);
+        let (values, _subscription) = record(&state);
+
+        state.set(1);
+        state.update(|value| *value += 1);
+        assert_eq!(*values.borrow(), [1, 2]);
+        assert_eq!(state.get(), 2);
+    }
+
+    #[test]
+    fn mapped_state_follows_its_source() {
+        let count = State::new(1);
+        let label = count.map(|count| format!("{count} contacts"));
+        let (values, _subscription) = record(&label);
+        assert_eq!(label.get(), "1 contacts");
+
+        count.set(2);
+        assert_eq!(label.get(), "2 contacts");
+        assert_eq!(*values.borrow(), ["2 contacts"]);
+    }
+
+    #[test]
+    fn combined_state_follows_both_sources() {
+        let name = State::new(String::new());
+        let is_saving = State::new(false);
+        let can_save = State::combine(&name, &is_saving, |name, is_saving| {
+            !name.is_empty() && !is_saving
+        });
+        assert!(!can_save.get());
+
+        name.set("Alice".to_string());
+        assert!(can_save.get());
+        is_saving.set(true);
+        assert!(!can_save.get());
+    }
+
+    #[test]
+    fn dropped_subscription_is_not_called() {
+        let state = State::new(0);
+        let (values, subscription) = record(&state);
+
+        state.set(1);
+        drop(subscription);
+        state.set(2);
+        assert_eq!(*values.borrow(), [1]);
+    }
+
+    #[test]
+    fn forgotten_subscription_keeps_being_called() {
+        let state = State::new(0);
+        let (values, subscription) = record(&state);
+        subscription.forget();
+
+        state.set(1);
+        assert_eq!(*values.borrow(), [1]);
+    }
+}
@@ -0,0 +1,367 @@
+//! Helpers for testing navigation logic without a display. This module is
+//! only available with the `testing` feature.
+//!
+//! GTK can only be used from the thread that initialized it so tests using
+//! the harness should be run with `--test-threads=1`.
+//!
+//! # Example
+//! ```
+//! fn opens_contact() {
+//!     let harness = rose::testing::RouterHarness::new();
+//!     harness.router().add_route::<ContactsRoute>();
+//!     harness.router().add_route::<ContactRoute>();
+//!
+//!     harness.visit::<ContactsRoute>(None);
+//!     harness.visit::<ContactRoute>(Some("alice".to_string()));
+//!
+//!     assert_eq!(harness.visible_route().as_deref(), Some("contacts.contact"));
+//!     assert_eq!(harness.visible_parameter::<ContactRoute>(), Some("alice".to_string()));
+//!     assert!(harness.can_go_back());
+//!     assert!(!harness.is_showing_error());
+//! }
+//! ```
+use std::cell::RefCell;
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use adw::prelude::*;
+use gtk::{gio, glib};
+
+use crate::prelude::*;
+use crate::router::history;
+use crate::{
+    Application, HistoryEntry, NotificationBackend, PageRoute, RouteNotification, Router,
+    TypedSettings, NAVIGATE_ACTION,
+};
+
+/// The broadway display used when the test environment doesn't pick one
+const BROADWAY_DISPLAY: u16 = 94;
+
+static BROADWAY_DAEMON: Mutex<Option<Child>> = Mutex::new(None);
+
+/// Initializes GTK and libadwaita without needing a real display. When
+/// `GDK_BACKEND` isn't set this starts a `gtk4-broadwayd` daemon and points
+/// GTK at it. Calling this more than once is a no-op.
+pub fn init_headless() {
+    if gtk::is_initialized() {
+        return;
+    }
+
+    if std::env::var_os("GDK_BACKEND").is_none() {
+        let display = std::env::var("BROADWAY_DISPLAY").unwrap_or_else(|_| {
+            let display = format!(":{BROADWAY_DISPLAY}");
+            std::env::set_var("BROADWAY_DISPLAY", &display);
+            display
+        });
+        std::env::set_var("GDK_BACKEND", "broadway");
+        start_broadway_daemon(&display);
+    }
+
+    adw::init().expect("Failed to initialize libadwaita, is a display available?");
+    crate::init();
+}
+
+fn start_broadway_daemon(display: &str) {
+    let mut daemon = BROADWAY_DAEMON.lock().unwrap();
+    if daemon.is_some() {
+        return;
+    }
+    let mut child = Command::new("gtk4-broadwayd")
+        .arg(display)
+        .spawn()
+        .unwrap_or_else(|error| {
+            panic!("Failed to start gtk4-broadwayd ({error}), install it or set GDK_BACKEND")
+        });
+
+    // broadwayd serves its web client on port 8080 + display so we
+    // can use that to know when its ready for connections
+    let port = display
+        .trim_start_matches(':')
+        .parse::<u16>()
+        .map(|display| 8080 + display)
+        .unwrap_or(8080);
+    let mut ready = false;
+    for _ in 0..50 {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            ready = true;
+            break;
+        }
+        if let Ok(Some(status)) = child.try_wait() {
+            panic!("gtk4-broadwayd exited before it was ready: {status}");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    if !ready {
+        let _ = child.kill();
+        panic!("gtk4-broadwayd didn't start listening on port {port}");
+    }
+
+    *daemon = Some(child);
+    unsafe {
+        atexit(stop_broadway_daemon);
+    }
+}
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+}
+
+/// Kills the daemon when the test binary exits so test runs don't leave
+/// daemons behind
+extern "C" fn stop_broadway_daemon() {
+    if let Ok(mut daemon) = BROADWAY_DAEMON.lock() {
+        if let Some(mut child) = daemon.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Runs the default main context until there is nothing left to do
+pub fn iterate() {
+    let context = glib::MainContext::default();
+    while context.iteration(false) {}
+}
+
+/// Builds an `Application` and a `Router` that can be navigated and
+/// inspected from tests.
+pub struct RouterHarness {
+    app: Application,
+    router: Router,
+    window: adw::Window,
+}
+
+impl RouterHarness {
+    /// Creates a new harness with the application id `org.rose.Test`
+    pub fn new() -> Self {
+        Self::with_application_id("org.rose.Test")
+    }
+
+    /// Creates a new harness. The application is registered and made the
+    /// default application so its actions can be activated and
+    /// `rose::visit` and friends work in the code under test.
+    pub fn with_application_id(application_id: &str) -> Self {
+        init_headless();
+
+        let app = glib::Object::builder::<Application>()
+            .property("application-id", application_id)
+            .property("flags", gio::ApplicationFlags::NON_UNIQUE)
+            .build();
+        app.set_default();
+        app.register(None::<&gio::Cancellable>)
+            .expect("Failed to register the application");
+
+        let router = glib::Object::new::<Router>();
+        if router.child().is_none() {
+            router.set_child(Some(&router.view()));
+        }
+        if !app.has_dependency::<Router>() {
+            app.add_dependency(&router);
+        }
+
+        let window = adw::Window::builder()
+            .application(&app)
+            .content(&router)
+            .build();
+        window.present();
+        iterate();
+
+        Self {
+            app,
+            router,
+            window,
+        }
+    }
+
+    pub fn app(&self) -> &Application {
+        &self.app
+    }
+
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    pub fn window(&self) -> &adw::Window {
+        &self.window
+    }
+
+    /// Visits a route and waits for the navigation to finish
+    pub fn visit<R>(&self, parameter: Option<R::Parameter>)
+    where
+        R: PageRoute + 'static,
+    {
+        self.router.visit::<R>(parameter);
+        iterate();
+    }
+
+    /// Navigates back and waits for the navigation to finish
+    pub fn back(&self) {
+        self.router.back();
+        iterate();
+    }
+
+    /// Gets the page that is currently visible
+    pub fn visible_page(&self) -> Option<adw::NavigationPage> {
+        self.router.view().visible_page()
+    }
+
+    /// Gets the route of the page that is currently visible
+    pub fn visible_route(&self) -> Option<String> {
+        self.visible_page()
+            .and_then(|page| history::page_route(&page))
+    }
+
+    /// Gets the parameter of the visible page. This returns `None` if the
+    /// visible page wasn't built by `R`
+    pub fn visible_parameter<R>(&self) -> Option<R::Parameter>
+    where
+        R: PageRoute + 'static,
+    {
+        let page = self.visible_page()?;
+        if history::page_route(&page).as_deref() != Some(R::route()) {
+            return None;
+        }
+        history::page_parameter(&page).and_then(|variant| variant.get::<R::Parameter>())
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.router.can_go_back()
+    }
+
+    /// Checks if the error page is the visible page
+    pub fn is_showing_error(&self) -> bool {
+        self.visible_page()
+            .map(|page| history::is_error_page(&page))
+            .unwrap_or(false)
+    }
+
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.router.history()
+    }
+}
+
+impl Default for RouterHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for RouterHarness {
+    fn drop(&mut self) {
+        self.window.destroy();
+        iterate();
+    }
+}
+
+/// A stand-in for the desktop notification service that records the
+/// notifications the application sends so tests can inspect and click
+/// them.
+///
+/// # Example
+/// ```
+/// let harness = RouterHarness::new();
+/// let notifications = NotificationRecorder::install(harness.app());
+///
+/// send_birthday_reminder(harness.app());
+/// notifications.click(0);
+/// iterate();
+///
+/// assert_eq!(harness.visible_route().as_deref(), Some("contacts.contact"));
+/// ```
+#[derive(Clone, Default)]
+pub struct NotificationRecorder {
+    sent: Rc<RefCell<Vec<SentNotification>>>,
+}
+
+/// A notification and the id it was sent with
+type SentNotification = (Option<String>, RouteNotification);
+
+impl NotificationRecorder {
+    /// Creates a recorder and adds it to the application as its
+    /// `NotificationBackend`, replacing any other backend
+    pub fn install(app: &Application) -> Self {
+        let recorder = Self::default();
+        app.replace_value_dependency::<dyn NotificationBackend>(Rc::new(recorder.clone()));
+        recorder
+    }
+
+    /// The notifications that have been sent and not withdrawn
+    pub fn notifications(&self) -> Vec<RouteNotification> {
+        self.sent
+            .borrow()
+            .iter()
+            .map(|(_, notification)| notification.clone())
+            .collect()
+    }
+
+    /// Gets a notification by the id it was sent with
+    pub fn find(&self, id: &str) -> Option<RouteNotification> {
+        self.sent
+            .borrow()
+            .iter()
+            .find(|(sent_id, _)| sent_id.as_deref() == Some(id))
+            .map(|(_, notification)| notification.clone())
+    }
+
+    /// Clicks a notification like the notification service would by
+    /// activating its default action
+    pub fn click(&self, index: usize) {
+        let target = self.sent.borrow()[index].1.default_target.clone();
+        if let Some(target) = target {
+            activate_navigate(&target);
+        }
+    }
+
+    /// Clicks a button on a notification
+    pub fn click_button(&self, index: usize, button: usize) {
+        let target = self.sent.borrow()[index].1.buttons[button].target.clone();
+        activate_navigate(&target);
+    }
+}
+
+fn activate_navigate(target: &glib::Variant) {
+    let app = Application::find();
+    app.activate_action(NAVIGATE_ACTION, Some(target));
+}
+
+impl NotificationBackend for NotificationRecorder {
+    fn send(&self, _app: &Application, id: Option<&str>, notification: &RouteNotification) {
+        let mut sent = self.sent.borrow_mut();
+        // Like the desktop a notification replaces one with the same id
+        if let Some(id) = id {
+            sent.retain(|(sent_id, _)| sent_id.as_deref() != Some(id));
+        }
+        sent.push((id.map(str::to_string), notification.clone()));
+    }
+
+    fn withdraw(&self, _app: &Application, id: &str) {
+        self.sent
+            .borrow_mut()
+            .retain(|(sent_id, _)| sent_id.as_deref() != Some(id));
+    }
+}
+
+/// Creates typed settings that are stored in memory. The schema is loaded
+/// from a folder of compiled schemas, like one made by running
+/// `glib-compile-schemas` in a build script, so it doesn't need to be
+/// installed.
+///
+/// # Example
+/// ```
+/// let settings = memory_settings::<AppSettings>(env!("OUT_DIR"));
+/// settings.set(AppSettings::DARK_MODE, true).unwrap();
+/// assert!(settings.get(AppSettings::DARK_MODE));
+/// ```
+pub fn memory_settings<S: TypedSettings>(schema_dir: impl AsRef<Path>) -> S {
+    let source = gio::SettingsSchemaSource::from_directory(
+        schema_dir,
+        gio::SettingsSchemaSource::default().as_ref(),
+        false,
+    )
+    .expect("Failed to load compiled settings schemas");
+    S::with_backend(&source, &gio::memory_settings_backend_new())
+}
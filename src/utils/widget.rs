@@ -1,9 +1,13 @@
+use adw::prelude::NavigationPageExt;
 use gtk::{
-    glib::{Cast, IsA},
-    prelude::WidgetExt,
+    glib::{self, Cast, IsA},
+    prelude::{ListModelExtManual, ObjectExt, WidgetExt},
 };
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use crate::prelude::RoseApplicationExt;
+use crate::prelude::{AsWeakRefExt, RoseApplicationExt};
+use crate::{Receiver, Subscription, TaskHandle};
 
 impl<T: IsA<gtk::Widget>> RoseWidgetExt for T {}
 
@@ -58,4 +62,227 @@ pub trait RoseWidgetExt: IsA<gtk::Widget> {
                 .build(),
         );
     }
+
+    /// Sets the title of the navigation page this widget is in. This is
+    /// useful for updating the title after data has been loaded async.
+    /// The new title will also show up in the router's history.
+    ///
+    /// # Example
+    /// ```
+    /// rose::spawn_async(load_contact(id), move |contact| {
+    ///     page.set_page_title(&contact.name);
+    /// });
+    /// ```
+    fn set_page_title(&self, title: &str) {
+        if let Some(page) = self.try_find_ancestor::<adw::NavigationPage>() {
+            page.set_title(title);
+        }
+    }
+
+    /// Like `rose::spawn_async` but the callback is given the widget
+    /// instead of capturing it. Only a weak reference to the widget is
+    /// kept. The task is cancelled when the widget is destroyed or the
+    /// page it's in is popped or replaced, so the callback never runs
+    /// against a dead widget.
+    ///
+    /// # Example
+    /// ```
+    /// label.spawn_async_for(load_contact(id), |label, contact| {
+    ///     label.set_text(&contact.name);
+    /// });
+    /// ```
+    #[track_caller]
+    fn spawn_async_for<T, F>(
+        &self,
+        future: impl std::future::Future<Output = T> + 'static + Send,
+        callback: F,
+    ) -> TaskHandle
+    where
+        Self: Sized + 'static,
+        T: Send + 'static,
+        F: FnOnce(&Self, T) + 'static,
+    {
+        // The callback owns the guard so the widget's handlers are
+        // disconnected when the task ends either way
+        let gone = Rc::new(RefCell::new(None::<WidgetGone>));
+        let guard = gone.clone();
+        let widget_ref = self.week_ref();
+        let handle = crate::spawn_async(future, move |value| {
+            guard.take();
+            if let Some(widget) = widget_ref.upgrade().filter(|w| !w.in_destruction()) {
+                callback(&widget, value);
+            }
+        });
+        gone.replace(Some(connect_widget_gone(
+            self.upcast_ref(),
+            handle.canceller(),
+        )));
+        handle
+    }
+
+    /// Attaches a callback to a receiver that is given the widget. Like
+    /// `spawn_async_for` only a weak reference to the widget is kept and
+    /// the callback is detached when the widget is destroyed or the page
+    /// it's in is popped or replaced. The callback is also detached when
+    /// the returned subscription is dropped, use `Subscription::forget` to
+    /// keep it for as long as the widget.
+    ///
+    /// # Example
+    /// ```
+    /// let (sender, receiver) = rose::Channel::new();
+    /// label
+    ///     .attach_receiver_for(&receiver, |label, progress: String| {
+    ///         label.set_text(&progress);
+    ///     })
+    ///     .forget();
+    /// ```
+    #[track_caller]
+    fn attach_receiver_for<T, F>(&self, receiver: &Receiver<T>, callback: F) -> Subscription
+    where
+        Self: IsA<glib::Object> + Sized + 'static,
+        T: Send + 'static,
+        F: Fn(&Self, T) + 'static,
+    {
+        let subscription = receiver.attach_weak(self, move |widget, value| {
+            if !widget.in_destruction() {
+                callback(widget, value);
+            }
+        });
+        let Some(source) = subscription.source().cloned() else {
+            return subscription;
+        };
+        // The subscription owns the guard instead of the callback, since
+        // the guard holds the callback's source. The widget's handlers are
+        // disconnected when it's detached.
+        let widget_gone = connect_widget_gone(self.upcast_ref(), move || source.destroy());
+        subscription.on_detach(move || drop(widget_gone))
+    }
+}
+
+/// Calls a callback once when a widget is destroyed or the navigation
+/// page it's in is popped or replaced. The signal handlers are
+/// disconnected when the callback is called or the guard is dropped.
+#[must_use = "The handlers are disconnected when the guard is dropped"]
+pub(crate) struct WidgetGone {
+    inner: Rc<Gone>,
+}
+
+struct Gone {
+    callback: RefCell<Option<Box<dyn FnOnce()>>>,
+    widget: glib::WeakRef<gtk::Widget>,
+    /// The one-shot `map` handler that looks up the page
+    map_handler: RefCell<Option<glib::SignalHandlerId>>,
+    handlers: RefCell<Vec<(glib::WeakRef<glib::Object>, glib::SignalHandlerId)>>,
+}
+
+impl Gone {
+    fn fire(&self) {
+        let callback = self.callback.take();
+        self.disconnect();
+        if let Some(callback) = callback {
+            callback();
+        }
+    }
+
+    fn disconnect(&self) {
+        if let (Some(widget), Some(id)) = (self.widget.upgrade(), self.map_handler.take()) {
+            widget.disconnect(id);
+        }
+        for (object, id) in self.handlers.take() {
+            if let Some(object) = object.upgrade() {
+                object.disconnect(id);
+            }
+        }
+    }
+
+    fn track(&self, object: &impl IsA<glib::Object>, id: glib::SignalHandlerId) {
+        self.handlers
+            .borrow_mut()
+            .push((object.upcast_ref::<glib::Object>().downgrade(), id));
+    }
+}
+
+impl Drop for WidgetGone {
+    fn drop(&mut self) {
+        self.inner.callback.take();
+        self.inner.disconnect();
+    }
+}
+
+/// Calls `callback` once when the widget is destroyed or the navigation
+/// page it's in is popped or replaced. If the widget isn't in a page yet
+/// the page is looked up when the widget is mapped.
+pub(crate) fn connect_widget_gone(
+    widget: &gtk::Widget,
+    callback: impl FnOnce() + 'static,
+) -> WidgetGone {
+    let inner = Rc::new(Gone {
+        callback: RefCell::new(Some(Box::new(callback))),
+        widget: widget.week_ref(),
+        map_handler: RefCell::default(),
+        handlers: RefCell::default(),
+    });
+
+    let gone = Rc::downgrade(&inner);
+    let id = widget.connect_destroy(move |_| {
+        if let Some(gone) = gone.upgrade() {
+            gone.fire();
+        }
+    });
+    inner.track(widget, id);
+
+    if let Some(page) = widget.try_find_ancestor::<adw::NavigationPage>() {
+        connect_page_gone(&inner, &page);
+    } else {
+        let gone = Rc::downgrade(&inner);
+        let id = widget.connect_map(move |widget| {
+            let Some(gone) = gone.upgrade() else {
+                return;
+            };
+            if let Some(id) = gone.map_handler.take() {
+                widget.disconnect(id);
+            }
+            if let Some(page) = widget.try_find_ancestor::<adw::NavigationPage>() {
+                connect_page_gone(&gone, &page);
+            }
+        });
+        inner.map_handler.replace(Some(id));
+    }
+
+    WidgetGone { inner }
+}
+
+fn connect_page_gone(inner: &Rc<Gone>, page: &adw::NavigationPage) {
+    let Some(view) = page.try_find_ancestor::<adw::NavigationView>() else {
+        return;
+    };
+
+    let gone = Rc::downgrade(inner);
+    let page_ref = page.week_ref();
+    let id = view.connect_popped(move |_, popped| {
+        if page_ref.upgrade().as_ref() == Some(popped) {
+            if let Some(gone) = gone.upgrade() {
+                gone.fire();
+            }
+        }
+    });
+    inner.track(&view, id);
+
+    // `NavigationView::replace` removes pages without emitting `popped`
+    let gone = Rc::downgrade(inner);
+    let page_ref = page.week_ref();
+    let id = view.connect_replaced(move |view| {
+        let on_stack = page_ref.upgrade().is_some_and(|page| {
+            view.navigation_stack()
+                .iter::<adw::NavigationPage>()
+                .filter_map(Result::ok)
+                .any(|on_stack| on_stack == page)
+        });
+        if !on_stack {
+            if let Some(gone) = gone.upgrade() {
+                gone.fire();
+            }
+        }
+    });
+    inner.track(&view, id);
 }
@@ -0,0 +1,80 @@
+use gtk::glib;
+use gtk::prelude::*;
+use rose_gtk::prelude::*;
+use rose_gtk::testing::RouterHarness;
+use rose_gtk::PageRoute;
+
+mod imp {
+    use gtk::glib;
+    use gtk::subclass::prelude::*;
+
+    #[derive(Default)]
+    pub struct ContactsRoute;
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ContactsRoute {
+        const NAME: &'static str = "TestContactsRoute";
+        type Type = super::ContactsRoute;
+    }
+
+    impl ObjectImpl for ContactsRoute {}
+
+    #[derive(Default)]
+    pub struct ContactRoute;
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ContactRoute {
+        const NAME: &'static str = "TestContactRoute";
+        type Type = super::ContactRoute;
+    }
+
+    impl ObjectImpl for ContactRoute {}
+}
+
+glib::wrapper! {
+    pub struct ContactsRoute(ObjectSubclass<imp::ContactsRoute>);
+}
+
+glib::wrapper! {
+    pub struct ContactRoute(ObjectSubclass<imp::ContactRoute>);
+}
+
+impl PageRoute for ContactsRoute {
+    type Parameter = ();
+
+    fn route() -> &'static str {
+        "contacts"
+    }
+
+    fn title(_parameter: Option<&Self::Parameter>) -> String {
+        "Contacts".to_string()
+    }
+
+    fn build(_parameter: Option<Self::Parameter>) -> impl IsA<gtk::Widget> {
+        gtk::Label::new(Some("Contacts"))
+    }
+}
+
+impl PageRoute for ContactRoute {
+    type Parameter = String;
+
+    fn route() -> &'static str {
+        "contacts.contact"
+    }
+
+    fn title(parameter: Option<&Self::Parameter>) -> String {
+        parameter.cloned().unwrap_or_default()
+    }
+
+    fn build(parameter: Option<Self::Parameter>) -> impl IsA<gtk::Widget> {
+        gtk::Label::new(parameter.as_deref())
+    }
+}
+
+/// Creates a harness with the contact routes
+pub fn harness() -> RouterHarness {
+    let harness = RouterHarness::new();
+    harness.router().add_route::<ContactsRoute>();
+    harness.router().add_route::<ContactRoute>();
+    harness
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use common::{harness, ContactRoute, ContactsRoute};
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use rose_gtk::prelude::*;
+use rose_gtk::testing::{iterate, NotificationRecorder, RouterHarness};
+use rose_gtk::{navigate_target, RouteNotification};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const NOTIFICATIONS_XML: &str = r#"
+<node>
+  <interface name="org.gtk.Notifications">
+    <method name="AddNotification">
+      <arg type="s" direction="in"/>
+      <arg type="s" direction="in"/>
+      <arg type="a{sv}" direction="in"/>
+    </method>
+    <method name="RemoveNotification">
+      <arg type="s" direction="in"/>
+      <arg type="s" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Starts a private session bus so notifications don't reach the desktop
+fn start_session_bus() -> Child {
+    let mut daemon = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start dbus-daemon");
+    let mut address = String::new();
+    BufReader::new(daemon.stdout.as_mut().unwrap())
+        .read_line(&mut address)
+        .expect("Failed to read the bus address");
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim());
+    // Send notifications with the `org.gtk.Notifications` interface which
+    // is what the stand-in below implements
+    std::env::set_var("GNOTIFICATION_BACKEND", "gtk");
+    daemon
+}
+
+/// Stands in for the desktop's notification service and collects the
+/// notifications it is sent
+fn serve_notifications() -> Arc<Mutex<Vec<glib::Variant>>> {
+    let connection = gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>)
+        .expect("Failed to connect to the session bus");
+    let node = gio::DBusNodeInfo::for_xml(NOTIFICATIONS_XML).unwrap();
+    let interface = node.lookup_interface("org.gtk.Notifications").unwrap();
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sent = received.clone();
+    connection
+        .register_object(
+            "/org/gtk/Notifications",
+            &interface,
+            move |_, _, _, _, method, parameters, invocation| {
+                if method == "AddNotification" {
+                    sent.lock().unwrap().push(parameters.child_value(2));
+                }
+                invocation.return_value(None);
+            },
+            |_, _, _, _, _| unreachable!(),
+            |_, _, _, _, _, _| unreachable!(),
+        )
+        .expect("Failed to export the notification service");
+    connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            Some(&("org.gtk.Notifications", 0u32).to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )
+        .expect("Failed to own org.gtk.Notifications");
+    received
+}
+
+fn birthday_notification() -> RouteNotification {
+    RouteNotification::new("Call Alice")
+        .body("Alice's birthday is today")
+        .default_route::<ContactRoute>(Some("alice".to_string()))
+        .button::<ContactsRoute>("Show contacts", None)
+}
+
+fn sends_desktop_notifications(harness: &RouterHarness, received: &Mutex<Vec<glib::Variant>>) {
+    harness
+        .app()
+        .send_route_notification(Some("birthday"), &birthday_notification());
+
+    let context = glib::MainContext::default();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while received.lock().unwrap().is_empty() && Instant::now() < deadline {
+        context.iteration(false);
+    }
+
+    let received = received.lock().unwrap();
+    let notification = received.first().expect("No notification was sent");
+    let notification = glib::VariantDict::new(Some(notification));
+    let lookup = |key: &str| notification.lookup_value(key, None);
+    assert_eq!(
+        lookup("title").and_then(|title| title.get::<String>()),
+        Some("Call Alice".to_string())
+    );
+    assert_eq!(
+        lookup("body").and_then(|body| body.get::<String>()),
+        Some("Alice's birthday is today".to_string())
+    );
+    assert_eq!(
+        lookup("default-action").and_then(|action| action.get::<String>()),
+        Some("app.navigate".to_string())
+    );
+    assert_eq!(
+        lookup("default-action-target").and_then(|target| target.as_variant()),
+        Some(navigate_target::<ContactRoute>(Some("alice".to_string())))
+    );
+
+    let buttons = lookup("buttons").expect("The notification has no buttons");
+    assert_eq!(buttons.n_children(), 1);
+    let button = glib::VariantDict::new(Some(&buttons.child_value(0)));
+    assert_eq!(
+        button
+            .lookup_value("label", None)
+            .and_then(|label| label.get::<String>()),
+        Some("Show contacts".to_string())
+    );
+    assert_eq!(
+        button
+            .lookup_value("target", None)
+            .and_then(|target| target.as_variant()),
+        Some(navigate_target::<ContactsRoute>(None))
+    );
+}
+
+fn clicking_a_notification_opens_its_route(harness: &RouterHarness) {
+    let notifications = NotificationRecorder::install(harness.app());
+    harness.visit::<ContactsRoute>(None);
+
+    harness
+        .app()
+        .send_route_notification(Some("birthday"), &birthday_notification());
+    assert_eq!(notifications.notifications().len(), 1);
+
+    notifications.click(0);
+    iterate();
+    assert_eq!(harness.visible_route().as_deref(), Some("contacts.contact"));
+    assert_eq!(
+        harness.visible_parameter::<ContactRoute>(),
+        Some("alice".to_string())
+    );
+
+    notifications.click_button(0, 0);
+    iterate();
+    assert_eq!(harness.visible_route().as_deref(), Some("contacts"));
+}
+
+/// GTK can only be used from one thread so the checks run one after
+/// another in a single test. The bus is started first so the application
+/// registers on it.
+#[test]
+fn notifications() {
+    let mut bus = start_session_bus();
+    let received = serve_notifications();
+
+    {
+        let harness = harness();
+        sends_desktop_notifications(&harness, &received);
+        clicking_a_notification_opens_its_route(&harness);
+    }
+
+    let _ = bus.kill();
+    let _ = bus.wait();
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{harness, ContactRoute, ContactsRoute};
+use rose_gtk::prelude::*;
+
+fn navigates_and_goes_back() {
+    let harness = harness();
+
+    harness.visit::<ContactsRoute>(None);
+    harness.visit::<ContactRoute>(Some("alice".to_string()));
+    assert_eq!(harness.visible_route().as_deref(), Some("contacts.contact"));
+    assert_eq!(
+        harness.visible_parameter::<ContactRoute>(),
+        Some("alice".to_string())
+    );
+    assert_eq!(harness.visible_parameter::<ContactsRoute>(), None);
+    assert!(harness.can_go_back());
+
+    let titles: Vec<_> = harness
+        .history()
+        .iter()
+        .map(|entry| entry.title.to_string())
+        .collect();
+    assert_eq!(titles, ["Contacts", "alice"]);
+
+    harness.back();
+    assert_eq!(harness.visible_route().as_deref(), Some("contacts"));
+    assert!(!harness.can_go_back());
+}
+
+fn shows_errors() {
+    struct NotFound;
+
+    impl rose_gtk::DisplayableError for NotFound {
+        fn title(&self) -> String {
+            "Not found".to_string()
+        }
+
+        fn body(&self) -> String {
+            String::new()
+        }
+    }
+
+    let harness = harness();
+    harness.visit::<ContactsRoute>(None);
+    assert!(!harness.is_showing_error());
+
+    harness.router().show_error(&NotFound);
+    rose_gtk::testing::iterate();
+    assert!(harness.is_showing_error());
+}
+
+/// GTK can only be used from one thread so the checks run one after
+/// another in a single test
+#[test]
+fn router_harness() {
+    navigates_and_goes_back();
+    shows_errors();
+}
@@ -0,0 +1,115 @@
+use gtk::glib;
+use gtk::prelude::*;
+use rose_gtk::prelude::*;
+use rose_gtk::testing::memory_settings;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
+
+rose_gtk::settings! {
+    struct TestSettings("org.rose.SettingsTest") {
+        const DARK_MODE: bool = "dark-mode";
+        const RECENT_SEARCHES: Vec<String> = "recent-searches";
+        const ZOOM: i32 = "zoom";
+    }
+}
+
+const SCHEMA: &str = r#"
+<schemalist>
+  <schema id="org.rose.SettingsTest" path="/org/rose/SettingsTest/">
+    <key name="dark-mode" type="b">
+      <default>false</default>
+    </key>
+    <key name="recent-searches" type="as">
+      <default>[]</default>
+    </key>
+    <key name="zoom" type="i">
+      <default>100</default>
+    </key>
+  </schema>
+</schemalist>
+"#;
+
+/// Compiles the test schema into a folder of its own for each test since
+/// the tests run at the same time
+fn compile_schema(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rose-settings-{test}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("org.rose.SettingsTest.gschema.xml"), SCHEMA).unwrap();
+    let status = Command::new("glib-compile-schemas")
+        .arg(&dir)
+        .status()
+        .expect("Failed to run glib-compile-schemas");
+    assert!(status.success(), "Failed to compile the test schema");
+    dir
+}
+
+fn iterate(context: &glib::MainContext) {
+    while context.iteration(false) {}
+}
+
+#[test]
+fn round_trips_values() {
+    let settings = memory_settings::<TestSettings>(compile_schema("round-trip"));
+
+    assert!(!settings.get(TestSettings::DARK_MODE));
+    assert_eq!(
+        settings.get(TestSettings::RECENT_SEARCHES),
+        Vec::<String>::new()
+    );
+    assert_eq!(settings.get(TestSettings::ZOOM), 100);
+
+    settings.set(TestSettings::DARK_MODE, true).unwrap();
+    settings
+        .set(
+            TestSettings::RECENT_SEARCHES,
+            vec!["alice".to_string(), "bob".to_string()],
+        )
+        .unwrap();
+    settings.set(TestSettings::ZOOM, 150).unwrap();
+
+    assert!(settings.get(TestSettings::DARK_MODE));
+    assert_eq!(
+        settings.get(TestSettings::RECENT_SEARCHES),
+        ["alice", "bob"]
+    );
+    assert_eq!(settings.get(TestSettings::ZOOM), 150);
+    assert_eq!(settings.settings().int("zoom"), 150);
+}
+
+#[test]
+fn notifies_changes_of_a_key() {
+    // Settings emit their signals on the thread default context of the
+    // thread that created them
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| {
+            let settings = memory_settings::<TestSettings>(compile_schema("changes"));
+            let changes = Rc::new(RefCell::new(Vec::new()));
+
+            let zoom_changes = changes.clone();
+            settings.connect_changed(TestSettings::ZOOM, move |_, zoom| {
+                zoom_changes.borrow_mut().push(zoom);
+            });
+
+            settings.set(TestSettings::ZOOM, 120).unwrap();
+            settings.set(TestSettings::DARK_MODE, true).unwrap();
+            settings.set(TestSettings::ZOOM, 80).unwrap();
+            iterate(&context);
+
+            assert_eq!(*changes.borrow(), [120, 80]);
+        })
+        .unwrap();
+}
+
+#[test]
+fn separate_backends_dont_share_values() {
+    let dir = compile_schema("backends");
+    let first = memory_settings::<TestSettings>(&dir);
+    let second = memory_settings::<TestSettings>(&dir);
+
+    first.set(TestSettings::ZOOM, 200).unwrap();
+
+    assert_eq!(second.get(TestSettings::ZOOM), 100);
+}